@@ -1,17 +1,55 @@
 use anyhow::{Context, Result};
-use globset::{Glob, GlobSetBuilder};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use ignore::WalkBuilder;
 use std::fs;
+use std::path::PathBuf;
 use sha2::{Sha256, Digest};
 
 pub fn compute_hash(patterns: &[String]) -> Result<String> {
     let mut hasher = Sha256::new();
-    let mut builder = GlobSetBuilder::new();
 
+    for relative_path in matching_files(patterns)? {
+        let content = fs::read(&relative_path)
+            .with_context(|| format!("Failed to read file: {:?}", relative_path))?;
+        // Hash both the path and the content to detect renames/moves
+        hasher.update(relative_path.to_string_lossy().as_bytes());
+        hasher.update(&content);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// File name used for per-task state. Instance names such as
+/// `build[--out=dist/x]` are made path-safe and kept distinct by a hash suffix.
+pub fn file_key(task: &str) -> String {
+    let safe: String = task
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' })
+        .collect();
+    if safe == task {
+        return safe;
+    }
+    let digest = format!("{:x}", Sha256::digest(task.as_bytes()));
+    format!("{}-{}", safe, &digest[..12])
+}
+
+/// Compiles a list of input patterns into a single matcher. A pattern
+/// ending in `/` (e.g. `src/`) stands for everything below that directory.
+pub fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
     for pat in patterns {
         builder.add(Glob::new(pat)?);
+        if pat.ends_with('/') {
+            builder.add(Glob::new(&format!("{}**", pat))?);
+        }
     }
-    let glob_set = builder.build()?;
+    Ok(builder.build()?)
+}
+
+/// Lists project files (relative to the root) matching any of the patterns.
+pub fn matching_files(patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let glob_set = build_glob_set(patterns)?;
+    let mut files = Vec::new();
 
     // Walk the project directory, respecting .gitignore but finding matches
     let walker = WalkBuilder::new("./")
@@ -26,14 +64,10 @@ pub fn compute_hash(patterns: &[String]) -> Result<String> {
             let relative_path = path.strip_prefix("./").unwrap_or(path);
             
             if glob_set.is_match(relative_path) {
-                let content = fs::read(path)
-                    .with_context(|| format!("Failed to read file: {:?}", path))?;
-                // Hash both the path and the content to detect renames/moves
-                hasher.update(relative_path.to_string_lossy().as_bytes());
-                hasher.update(&content);
+                files.push(relative_path.to_path_buf());
             }
         }
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
-}
\ No newline at end of file
+    Ok(files)
+}
@@ -13,6 +13,7 @@ pub struct Cli {
 }
 
 #[derive(Subcommand, Clone, Debug)]
+#[allow(clippy::large_enum_variant)] // Built once per process; `run` simply has many flags
 pub enum Command {
     /// Initialize a new Zetten (ztn) project
     Init {
@@ -22,7 +23,7 @@ pub enum Command {
 
     /// Run tasks in parallel with caching
     Run {
-        /// Names of the tasks to run
+        /// Names of the tasks to run; globs like 'lint-*' select every matching task
         tasks: Vec<String>,
 
         /// Number of parallel workers
@@ -41,13 +42,82 @@ pub enum Command {
         #[arg(last = true)]
         args: Vec<String>,
 
-        /// Filter tasks by a specific tag (e.g., --tag ci)
+        /// Filter tasks by a tag expression (e.g., --tag "ci and not slow")
         #[arg(short, long)]
         tag: Option<String>,
+
+        /// Apply a [profiles.<name>] section (falls back to ZTN_PROFILE)
+        #[arg(short, long)]
+        profile: Option<String>,
+
+        /// Answer yes to every task confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Keep running tasks that don't depend on a failed one
+        #[arg(long)]
+        keep_going: bool,
+
+        /// Only run matrix combinations with this value (e.g. --matrix python=3.12)
+        #[arg(long, value_parser = parse_key_val)]
+        matrix: Vec<(String, String)>,
+
+        /// Re-run what failed or was cancelled last time, with the same args and vars
+        #[arg(long)]
+        failed: bool,
+
+        /// Run just the named tasks, without their depends_on
+        #[arg(long)]
+        only: bool,
+
+        /// Start the plan at this task, skipping everything ordered before it
+        #[arg(long, value_name = "TASK")]
+        from: Option<String>,
+
+        /// End the plan at this task, skipping everything ordered after it
+        #[arg(long, value_name = "TASK")]
+        until: Option<String>,
+
+        /// Only run tasks whose inputs changed according to git, plus their dependents
+        #[arg(long)]
+        affected: bool,
+
+        /// Also count changes committed since this ref (e.g. origin/main)
+        #[arg(long, value_name = "REF", requires = "affected")]
+        base: Option<String>,
+
+        /// Run every task whose name matches this regex (e.g. --match '^lint-')
+        #[arg(short = 'm', long = "match", value_name = "REGEX")]
+        matches: Vec<String>,
+
+        /// Succeed without running anything when a pattern matches no task
+        #[arg(long)]
+        allow_empty: bool,
+    },
+
+    /// Re-run what failed or was cancelled in the last run (same as `run --failed`)
+    Rerun {
+        /// Number of parallel workers
+        #[arg(short, long, default_value = "auto")]
+        workers: String,
+
+        /// Keep running tasks that don't depend on a failed one
+        #[arg(long)]
+        keep_going: bool,
+    },
+
+    /// Manage git hooks that run tasks listing them in `hooks`
+    Hooks {
+        #[command(subcommand)]
+        action: HooksAction,
     },
 
     /// List all available tasks
-    Tasks,
+    Tasks {
+        /// Only list tasks matching a tag expression (e.g. "ci and not slow")
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
 
     /// Watch for changes and re-run tasks
     Watch { tasks: Vec<String> },
@@ -62,6 +132,24 @@ pub enum Command {
     Completions { shell: Shell },
 }
 
+#[derive(Subcommand, Clone, Debug)]
+pub enum HooksAction {
+    /// Write hook shims, keeping any existing hooks (default: every hook a task lists)
+    Install { hooks: Vec<String> },
+
+    /// Remove the shims and restore the hooks they replaced
+    Uninstall,
+
+    /// Run the tasks for a hook (what the shims call)
+    Run {
+        hook: String,
+
+        /// The arguments git passed to the hook
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
 fn parse_key_val(s: &str) -> Result<(String, String), String> {
     s.split_once('=')
         .map(|(k, v)| (k.to_string(), v.to_string()))
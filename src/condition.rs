@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use crate::config::{interpolate, Condition, TaskConfig};
+use crate::runner::shell_command;
+
+/// Decides whether a task should be skipped. Returns the reason to show in
+/// the log when it should, `None` when it should run.
+pub fn skip_reason(task: &TaskConfig, vars: &HashMap<String, String>) -> Result<Option<String>> {
+    if let Some(cond) = &task.run_if {
+        if !holds(cond, vars)? {
+            return Ok(Some(format!("run_if not met: {}", describe(cond))));
+        }
+    }
+    if let Some(cond) = &task.skip_if {
+        if holds(cond, vars)? {
+            return Ok(Some(format!("skip_if met: {}", describe(cond))));
+        }
+    }
+    Ok(None)
+}
+
+fn holds(cond: &Condition, vars: &HashMap<String, String>) -> Result<bool> {
+    // `equals` without `env` is rejected by `Config::validate`
+    if let Some(name) = &cond.env {
+        match (vars.get(name), &cond.equals) {
+            (None, _) => return Ok(false),
+            (Some(actual), Some(expected)) if actual != &interpolate(expected, vars) => return Ok(false),
+            _ => {}
+        }
+    }
+
+    if let Some(path) = &cond.exists {
+        if !Path::new(&interpolate(path, vars)).exists() {
+            return Ok(false);
+        }
+    }
+
+    if let Some(pattern) = &cond.glob {
+        let pattern = interpolate(pattern, vars);
+        if crate::cache::matching_files(&[pattern])?.is_empty() {
+            return Ok(false);
+        }
+    }
+
+    if let Some(cmd) = &cond.cmd {
+        let status = shell_command(&interpolate(cmd, vars))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| anyhow!("Failed to run condition '{}': {}", cmd, e))?;
+        if !status.success() {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn describe(cond: &Condition) -> String {
+    let mut parts = Vec::new();
+    if let Some(name) = &cond.env {
+        match &cond.equals {
+            Some(v) => parts.push(format!("{}={}", name, v)),
+            None => parts.push(format!("{} is set", name)),
+        }
+    }
+    if let Some(p) = &cond.exists {
+        parts.push(format!("'{}' exists", p));
+    }
+    if let Some(g) = &cond.glob {
+        parts.push(format!("'{}' has matches", g));
+    }
+    if let Some(c) = &cond.cmd {
+        parts.push(format!("`{}` succeeds", c));
+    }
+    parts.join(" and ")
+}
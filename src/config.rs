@@ -2,8 +2,8 @@ use crate::root::ConfigSource;
 use anyhow::{anyhow, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
-use serde::Deserialize;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 
 lazy_static! {
@@ -11,16 +11,71 @@ lazy_static! {
     static ref RE_VAR_PLAIN: Regex = Regex::new(r"\$\{([^}]+)\}").unwrap();
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Default)]
 pub struct Config {
     pub tasks: HashMap<String, TaskConfig>,
     #[serde(default)]
     pub vars: HashMap<String, String>, // Global variables from TOML
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>, // [profiles.<name>] overrides
+    #[serde(default)]
+    pub resources: HashMap<String, u32>, // Capacity of counted resources
+}
+
+/// Environment-specific overrides selected via `--profile` or `ZTN_PROFILE`.
+#[derive(Deserialize, Clone, Default)]
+pub struct Profile {
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+
+    /// Exported to every task process while the profile is active
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    /// Partial task tables merged field-by-field over the base task
+    #[serde(default)]
+    pub tasks: HashMap<String, toml::Table>,
+}
+
+/// A task command, either shared by every OS or chosen per platform:
+/// `cmd = "make"` or `cmd.linux = "..."`, `cmd.windows = "..."`.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum CommandSpec {
+    Shared(String),
+    PerPlatform(PlatformCommands),
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PlatformCommands {
+    pub linux: Option<String>,
+    pub macos: Option<String>,
+    pub windows: Option<String>,
+    pub default: Option<String>, // Fallback for any OS without its own entry
+}
+
+impl CommandSpec {
+    /// Picks the command for the OS we are running on.
+    pub fn for_current_platform(&self) -> Option<&str> {
+        match self {
+            CommandSpec::Shared(cmd) => Some(cmd),
+            CommandSpec::PerPlatform(p) => {
+                let specific = match std::env::consts::OS {
+                    "linux" => p.linux.as_deref(),
+                    "macos" => p.macos.as_deref(),
+                    "windows" => p.windows.as_deref(),
+                    _ => None,
+                };
+                specific.or(p.default.as_deref())
+            }
+        }
+    }
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct TaskConfig {
-    pub cmd: Option<String>,
+    pub cmd: Option<CommandSpec>,
     pub script: Option<String>, // Python script execution
     pub interactive: Option<bool>, // Interactive mode
     pub setup: Option<String>, // Explicit setup task
@@ -36,14 +91,133 @@ pub struct TaskConfig {
     #[serde(default)]
     pub tags: Vec<String>,
 
+    #[serde(default)]
+    pub hooks: Vec<String>, // Git hooks that run this task, e.g. "pre-commit"
+
     #[serde(default)]
     pub allow_exit_codes: Vec<i32>,
 
     #[serde(default)]
-    pub depends_on: Vec<String>,
+    pub depends_on: Vec<Dependency>,
+
+    #[serde(default)]
+    pub after: Vec<String>, // Ordering only: waits for these if they are planned anyway
+
+    #[serde(default)]
+    pub optional_depends_on: Vec<String>, // Like `after`, but their failure is ignored
 
     #[serde(default)]
     pub ignore_errors: bool,
+
+    pub run_if: Option<Condition>, // Only run when this holds
+    pub skip_if: Option<Condition>, // Skip when this holds
+
+    #[serde(default)]
+    pub aliases: Vec<String>, // Alternative names accepted on the CLI
+
+    #[serde(default)]
+    pub private: bool, // Dependency-only helper, hidden from listings
+
+    pub confirm: Option<String>, // Prompt shown before the task runs
+
+    #[serde(default)]
+    pub resources: ResourceClaims, // Never held by two tasks beyond capacity
+    pub lock: Option<String>, // Shorthand for an exclusive resource
+
+    pub weight: Option<u32>, // Worker slots consumed while running (default 1)
+    pub parallel: Option<bool>, // `false` runs the task alone
+
+    #[serde(default)]
+    pub service: bool, // Long-running: kept in the background while dependents run
+    pub ready_when: Option<ReadyCheck>, // When a service counts as started
+
+    #[serde(default)]
+    pub matrix: BTreeMap<String, Vec<String>>, // One instance per combination, via ${matrix.<axis>}
+
+    #[serde(default)]
+    pub pass_files: bool, // Append the changed files matching `inputs` as arguments
+
+    // Set on instances created for parameterized dependencies and matrices
+    #[serde(skip)]
+    pub bound_args: Vec<String>,
+    #[serde(skip)]
+    pub bound_vars: HashMap<String, String>,
+    #[serde(skip)]
+    pub matrix_instances: Vec<String>, // Selected combinations of a matrix task
+    #[serde(skip)]
+    pub passed_files: Vec<String>, // What `pass_files` appends this run
+}
+
+/// An entry of `depends_on`: a task name, or a task run with its own
+/// arguments and variables, e.g. `{ task = "build", args = ["--release"] }`.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Dependency {
+    Name(String),
+    Instance {
+        task: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        vars: HashMap<String, String>,
+    },
+}
+
+impl Dependency {
+    /// The task this entry refers to.
+    pub fn task(&self) -> &str {
+        match self {
+            Dependency::Name(name) => name,
+            Dependency::Instance { task, .. } => task,
+        }
+    }
+}
+
+/// `resources = ["db", "port:8000"]` claims one unit of each;
+/// `resources = { gpu_slots = 1, memory = 4 }` claims explicit amounts.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum ResourceClaims {
+    Names(Vec<String>),
+    Counted(HashMap<String, u32>),
+}
+
+impl Default for ResourceClaims {
+    fn default() -> Self {
+        ResourceClaims::Names(Vec::new())
+    }
+}
+
+/// A runtime predicate for `run_if` / `skip_if`. Every field that is set
+/// must hold for the condition to be true.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// Variable (resolved through the usual tiers) that must be set...
+    pub env: Option<String>,
+    /// ...and, if given, equal to this value
+    pub equals: Option<String>,
+    /// Path that must exist
+    pub exists: Option<String>,
+    /// Shell predicate that must exit with code 0
+    pub cmd: Option<String>,
+    /// Glob that must match at least one project file
+    pub glob: Option<String>,
+}
+
+/// When a `service` task is ready for its dependents. Every check that is
+/// set must hold; they are polled until then or until `timeout` runs out.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ReadyCheck {
+    /// Local TCP port that accepts connections
+    pub port: Option<u16>,
+    /// `http://` URL that answers with a 2xx or 3xx status
+    pub http: Option<String>,
+    /// Pattern that some line of the service's output matches
+    pub log_regex: Option<String>,
+    /// Seconds to wait before giving up (default 60)
+    pub timeout: Option<u64>,
 }
 
 fn default_description() -> String {
@@ -51,6 +225,59 @@ fn default_description() -> String {
 }
 
 impl TaskConfig {
+    /// Tasks that must succeed before this one starts: its setup, then `depends_on`.
+    pub fn prerequisites(&self) -> Vec<String> {
+        let deps = self.depends_on.iter().map(|d| d.task().to_string());
+        self.setup.iter().cloned().chain(deps).collect()
+    }
+
+    /// The run's variables with this instance's own `vars` on top.
+    pub fn effective_vars(&self, all_vars: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut vars = all_vars.clone();
+        vars.extend(self.bound_vars.clone());
+        vars
+    }
+
+    /// A task without a command only groups its dependencies.
+    pub fn is_group(&self) -> bool {
+        self.cmd.is_none() && self.script.is_none()
+    }
+
+    /// What `--tag` matches against: the task's tags plus its git hooks.
+    pub fn selection_tags(&self) -> Vec<String> {
+        self.tags.iter().chain(&self.hooks).cloned().collect()
+    }
+
+    /// Tasks this one waits for only when they are in the plan anyway.
+    pub fn soft_dependencies(&self) -> Vec<String> {
+        self.after.iter().chain(&self.optional_depends_on).cloned().collect()
+    }
+
+    /// Worker slots this task occupies out of a `budget` of `--workers`.
+    pub fn slot_weight(&self, budget: u32) -> u32 {
+        if self.parallel == Some(false) {
+            return budget;
+        }
+        self.weight.unwrap_or(1).clamp(1, budget.max(1))
+    }
+
+    /// All resources this task holds while running, with amounts.
+    pub fn resource_claims(&self) -> HashMap<String, u32> {
+        let mut claims: HashMap<String, u32> = match &self.resources {
+            ResourceClaims::Names(names) => names.iter().map(|n| (n.clone(), 1)).collect(),
+            ResourceClaims::Counted(counted) => counted.clone(),
+        };
+        if let Some(lock) = &self.lock {
+            claims.entry(lock.clone()).or_insert(1);
+        }
+        claims
+    }
+
+    /// The raw (uninterpolated) command for this platform, if any.
+    pub fn platform_cmd(&self) -> Option<&str> {
+        self.cmd.as_ref().and_then(|c| c.for_current_platform())
+    }
+
     /// Resolves the command string using the hierarchy: CLI > TOML > ENV
     pub fn resolve_cmd(&self, extra_args: &[String], all_vars: &HashMap<String, String>) -> String {
         if let Some(script) = &self.script {
@@ -62,30 +289,16 @@ impl TaskConfig {
             return format!("python {}", script);
         }
 
-        let mut resolved = self.cmd.clone().unwrap_or_default();
+        // 1-2. Resolve ${VAR:-default} and plain ${VAR}
+        let all_vars = self.effective_vars(all_vars);
+        let mut resolved = interpolate(self.platform_cmd().unwrap_or_default(), &all_vars);
 
-        // 1. Resolve ${VAR:-default}
-        resolved = RE_VAR_DEFAULT
-            .replace_all(&resolved, |caps: &regex::Captures| {
-                let var_name = &caps[1];
-                let default_val = &caps[2];
-                all_vars.get(var_name).cloned().unwrap_or_else(|| default_val.to_string())
-            })
-            .to_string();
-
-        // 2. Resolve plain ${VAR}
-        resolved = RE_VAR_PLAIN
-            .replace_all(&resolved, |caps: &regex::Captures| {
-                let var_name = &caps[1];
-                all_vars.get(var_name).cloned().unwrap_or_else(|| format!("${{{}}}", var_name))
-            })
-            .to_string();
-
-        // 3. Append forwarded positional arguments
-        let positional_args: Vec<String> = extra_args
+        // 3. Append the instance's own arguments, then forwarded positional ones
+        let positional_args: Vec<String> = self
+            .bound_args
             .iter()
-            .filter(|a| !a.contains('='))
             .cloned()
+            .chain(extra_args.iter().filter(|a| !a.contains('=')).cloned())
             .collect();
 
         if !positional_args.is_empty() {
@@ -97,20 +310,36 @@ impl TaskConfig {
     }
 }
 
+/// Expands `${VAR:-default}` and `${VAR}` references; unknown plain
+/// variables are left untouched for the shell to handle.
+pub fn interpolate(input: &str, all_vars: &HashMap<String, String>) -> String {
+    let resolved = RE_VAR_DEFAULT
+        .replace_all(input, |caps: &regex::Captures| {
+            let var_name = &caps[1];
+            let default_val = &caps[2];
+            all_vars.get(var_name).cloned().unwrap_or_else(|| default_val.to_string())
+        })
+        .to_string();
+
+    RE_VAR_PLAIN
+        .replace_all(&resolved, |caps: &regex::Captures| {
+            let var_name = &caps[1];
+            all_vars.get(var_name).cloned().unwrap_or_else(|| format!("${{{}}}", var_name))
+        })
+        .to_string()
+}
+
 impl Config {
     pub fn load(source: &ConfigSource) -> Result<Self> {
         // 1. Start with Global Config (Base Layer) from ~/.config/zetten.toml
         let mut final_config = if let Some(global_path) = crate::root::get_global_config_path() {
             if let Ok(contents) = fs::read_to_string(global_path) {
-                toml::from_str::<Config>(&contents).unwrap_or(Config {
-                    tasks: HashMap::new(),
-                    vars: HashMap::new(),
-                })
+                toml::from_str::<Config>(&contents).unwrap_or_default()
             } else {
-                Config { tasks: HashMap::new(), vars: HashMap::new() }
+                Config::default()
             }
         } else {
-            Config { tasks: HashMap::new(), vars: HashMap::new() }
+            Config::default()
         };
 
         // 2. Load Local Config (Priority Layer)
@@ -136,18 +365,223 @@ impl Config {
         for (name, task) in local_config.tasks {
             final_config.tasks.insert(name, task);
         }
+        for (name, profile) in local_config.profiles {
+            final_config.profiles.insert(name, profile);
+        }
+        for (name, capacity) in local_config.resources {
+            final_config.resources.insert(name, capacity);
+        }
 
         Ok(final_config)
     }
 
+    /// Merges the named profile's task overrides into the task table and
+    /// returns the profile so its vars/env can join the variable merge.
+    pub fn apply_profile(&mut self, name: &str) -> Result<Profile> {
+        let profile = self.profiles.get(name).cloned().ok_or_else(|| {
+            let mut known: Vec<_> = self.profiles.keys().map(|s| s.as_str()).collect();
+            known.sort();
+            anyhow!(
+                "USER_ERROR: Unknown profile '{}' (available: {})",
+                name,
+                if known.is_empty() { "none".to_string() } else { known.join(", ") }
+            )
+        })?;
+
+        for (task_name, overrides) in &profile.tasks {
+            let base = self.tasks.get(task_name).ok_or_else(|| {
+                anyhow!("USER_ERROR: Profile '{}' overrides unknown task '{}'", name, task_name)
+            })?;
+            let mut table = toml::Table::try_from(base)?;
+            for (key, value) in overrides {
+                table.insert(key.clone(), value.clone());
+            }
+            let merged: TaskConfig = toml::Value::Table(table).try_into().map_err(|e| {
+                anyhow!("USER_ERROR: Invalid override for task '{}' in profile '{}': {}", task_name, name, e)
+            })?;
+            self.tasks.insert(task_name.clone(), merged);
+        }
+
+        Ok(profile)
+    }
+
+    /// Turns every parameterized `depends_on` entry into a dependency on a
+    /// private copy of the task bound to those args and vars. Equal entries
+    /// share one instance, so it still runs once per run.
+    pub fn instantiate_dependencies(&mut self) {
+        let mut pending: Vec<String> = self.tasks.keys().cloned().collect();
+        while let Some(name) = pending.pop() {
+            let mut deps = self.tasks[&name].depends_on.clone();
+            for dep in deps.iter_mut() {
+                let Dependency::Instance { task, args, vars } = dep else { continue };
+                if args.is_empty() && vars.is_empty() {
+                    *dep = Dependency::Name(task.clone());
+                    continue;
+                }
+                let mut sorted_vars: Vec<String> = vars.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                sorted_vars.sort();
+                let instance = format!("{}[{}]", task, args.iter().cloned().chain(sorted_vars).collect::<Vec<_>>().join(" "));
+                if !self.tasks.contains_key(&instance) {
+                    let mut copy = self.tasks[task.as_str()].clone();
+                    copy.bound_args = args.clone();
+                    copy.bound_vars = vars.clone();
+                    copy.private = true;
+                    copy.aliases.clear();
+                    self.tasks.insert(instance.clone(), copy);
+                    pending.push(instance.clone());
+                }
+                *dep = Dependency::Name(instance);
+            }
+            self.tasks.get_mut(&name).unwrap().depends_on = deps;
+        }
+    }
+
+    /// Expands each matrix task into one private instance per combination
+    /// allowed by `filter` (axis=value pairs; several values for one axis
+    /// are alternatives), with `${matrix.<axis>}` bound. Whatever depended
+    /// on a matrix task depends on all of its instances instead.
+    pub fn expand_matrices(&mut self, filter: &[(String, String)]) {
+        let allowed = |axis: &str, value: &str| {
+            let wanted: Vec<&(String, String)> = filter.iter().filter(|(a, _)| a == axis).collect();
+            wanted.is_empty() || wanted.iter().any(|(_, v)| v == value)
+        };
+
+        let mut bases: Vec<String> = self.tasks.iter().filter(|(_, t)| !t.matrix.is_empty()).map(|(n, _)| n.clone()).collect();
+        bases.sort();
+        let mut expanded: HashMap<String, Vec<String>> = HashMap::new();
+        for base in bases {
+            let mut combos: Vec<Vec<(String, String)>> = vec![Vec::new()];
+            for (axis, values) in &self.tasks[&base].matrix {
+                combos = combos
+                    .into_iter()
+                    .flat_map(|combo| {
+                        values.iter().filter(|v| allowed(axis, v)).map(move |v| {
+                            let mut combo = combo.clone();
+                            combo.push((axis.clone(), v.clone()));
+                            combo
+                        })
+                    })
+                    .collect();
+            }
+
+            let mut instances = Vec::new();
+            for combo in combos {
+                let label: Vec<String> = combo.iter().map(|(a, v)| format!("{}={}", a, v)).collect();
+                let name = format!("{}[{}]", base, label.join(" "));
+                let mut copy = self.tasks[&base].clone();
+                copy.matrix.clear();
+                copy.bound_vars.extend(combo.into_iter().map(|(a, v)| (format!("matrix.{}", a), v)));
+                copy.private = true;
+                copy.aliases.clear();
+                self.tasks.insert(name.clone(), copy);
+                instances.push(name);
+            }
+            self.tasks.get_mut(&base).unwrap().matrix_instances = instances.clone();
+            expanded.insert(base, instances);
+        }
+
+        let replace = |names: &[String]| -> Vec<String> {
+            names.iter().flat_map(|n| expanded.get(n).cloned().unwrap_or_else(|| vec![n.clone()])).collect()
+        };
+        for task in self.tasks.values_mut() {
+            task.depends_on = task
+                .depends_on
+                .iter()
+                .flat_map(|d| match expanded.get(d.task()) {
+                    // With no combination left the base stays, so planning it fails loudly
+                    Some(instances) if !instances.is_empty() => instances.iter().cloned().map(Dependency::Name).collect(),
+                    _ => vec![d.clone()],
+                })
+                .collect();
+            task.after = replace(&task.after);
+            task.optional_depends_on = replace(&task.optional_depends_on);
+        }
+    }
+
+    /// Maps a CLI name (task name or alias) to the canonical task name.
+    pub fn resolve_name<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        if self.tasks.contains_key(name) {
+            return Some(name);
+        }
+        self.tasks
+            .iter()
+            .find(|(_, t)| t.aliases.iter().any(|a| a == name))
+            .map(|(n, _)| n.as_str())
+    }
+
+    /// Names (and aliases) of tasks that may be invoked directly, sorted.
+    pub fn public_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .tasks
+            .iter()
+            .filter(|(_, t)| !t.private)
+            .flat_map(|(n, t)| std::iter::once(n.as_str()).chain(t.aliases.iter().map(|a| a.as_str())))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Sorted names of non-private tasks, for listings and selectors.
+    pub fn visible_tasks(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = self.tasks.iter().filter(|(_, t)| !t.private).map(|(n, _)| n).collect();
+        names.sort();
+        names
+    }
+
     pub fn validate(&self) -> Result<()> {
+        let mut seen_aliases: HashMap<&str, &str> = HashMap::new();
+        for (name, task) in &self.tasks {
+            for alias in &task.aliases {
+                if self.tasks.contains_key(alias) {
+                    return Err(anyhow!("USER_ERROR: Alias '{}' of task '{}' shadows an existing task", alias, name));
+                }
+                if let Some(other) = seen_aliases.insert(alias, name) {
+                    return Err(anyhow!("USER_ERROR: Alias '{}' is used by both '{}' and '{}'", alias, other, name));
+                }
+            }
+        }
+
         for name in self.tasks.keys() {
             let task = &self.tasks[name];
-            for dep in &task.depends_on {
+            for (resource, amount) in task.resource_claims() {
+                let capacity = self.resources.get(&resource).copied().unwrap_or(1);
+                if amount > capacity {
+                    return Err(anyhow!(
+                        "USER_ERROR: Task '{}' claims {} of resource '{}', but only {} available",
+                        name, amount, resource, capacity
+                    ));
+                }
+            }
+            for dep in task.prerequisites().iter().chain(&task.soft_dependencies()) {
                 if !self.tasks.contains_key(dep) {
                     return Err(anyhow!("USER_ERROR: Task '{}' depends on unknown task '{}'", name, dep));
                 }
             }
+            if let Some(hook) = task.hooks.iter().find(|h| !crate::hooks::SUPPORTED.contains(&h.as_str())) {
+                return Err(anyhow!("USER_ERROR: Task '{}' lists unsupported git hook '{}'", name, hook));
+            }
+            if let Some(check) = &task.ready_when {
+                if !task.service {
+                    return Err(anyhow!("USER_ERROR: Task '{}' has 'ready_when' but is not a service", name));
+                }
+                if let Some(pattern) = &check.log_regex {
+                    Regex::new(pattern)
+                        .map_err(|e| anyhow!("USER_ERROR: Invalid log_regex for task '{}': {}", name, e))?;
+                }
+                if check.http.as_ref().is_some_and(|url| !url.starts_with("http://")) {
+                    return Err(anyhow!("USER_ERROR: ready_when.http of task '{}' must be an http:// URL", name));
+                }
+            }
+            for (kind, cond) in [("run_if", &task.run_if), ("skip_if", &task.skip_if)] {
+                if cond.as_ref().is_some_and(|c| c.equals.is_some() && c.env.is_none()) {
+                    return Err(anyhow!("USER_ERROR: {} of task '{}' uses 'equals' without 'env'", kind, name));
+                }
+            }
+            for (kind, hook) in [("setup", &task.setup), ("teardown", &task.teardown)] {
+                if let Some(hook) = hook.as_ref().filter(|h| !self.tasks.contains_key(*h)) {
+                    return Err(anyhow!("USER_ERROR: Task '{}' uses unknown task '{}' as {}", name, hook, kind));
+                }
+            }
             self.check_cycles(name, &mut HashSet::new())?;
         }
         Ok(())
@@ -159,7 +593,7 @@ impl Config {
         }
         visited.insert(name.to_string());
         if let Some(task) = self.tasks.get(name) {
-            for dep in &task.depends_on {
+            for dep in task.prerequisites().iter().chain(&task.soft_dependencies()) {
                 self.check_cycles(dep, visited)?;
             }
         }
@@ -1,5 +1,4 @@
-use miette::Diagnostic;
-use std::path::PathBuf;
+use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
 
 #[derive(Error, Debug, Diagnostic)]
@@ -25,6 +24,27 @@ pub enum ZettenError {
     )]
     TaskNotFoundFuzzy(String, String),
 
+    #[error("No task matches '{0}'")]
+    #[diagnostic(
+        code(ztn::task::no_match),
+        help("Run `ztn tasks` to see the available names, or pass `--allow-empty` if matching nothing is fine.")
+    )]
+    NoTasksMatch(String),
+
+    #[error("Task '{0}' is private")]
+    #[diagnostic(
+        code(ztn::task::private),
+        help("Private tasks can only run as dependencies of other tasks. Run `ztn tasks` to see the public ones.")
+    )]
+    TaskPrivate(String),
+
+    #[error("Task '{0}' requires confirmation")]
+    #[diagnostic(
+        code(ztn::task::confirm),
+        help("No terminal is attached to answer the prompt. Re-run with `--yes` to confirm non-interactively.")
+    )]
+    ConfirmationRequired(String),
+
     #[error("Circular dependency detected")]
     #[diagnostic(
         code(ztn::graph::cycle),
@@ -32,6 +52,26 @@ pub enum ZettenError {
     )]
     CircularDependency(String),
 
+    #[error("Recursive ztn invocation detected")]
+    #[diagnostic(
+        code(ztn::task::recursion),
+        help("A task ends up running itself through a nested 'ztn run': {0}. Break the loop in the task's command.")
+    )]
+    RecursiveInvocation(String),
+
+    #[error("Invalid tag expression: {message}")]
+    #[diagnostic(
+        code(ztn::tag::syntax),
+        help("Combine tags with and/or/not (or +, ',' and !) and parentheses, e.g. \"(ci or nightly) and not slow\".")
+    )]
+    InvalidTagExpression {
+        #[source_code]
+        expr: String,
+        #[label("{message}")]
+        span: SourceSpan,
+        message: String,
+    },
+
     #[error("Task execution failed")]
     #[diagnostic(
         code(ztn::exec::failed),
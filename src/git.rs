@@ -0,0 +1,133 @@
+use crate::cache::build_glob_set;
+use crate::config::{Config, TaskConfig};
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const CHANGED_VAR: &str = "ZTN_CHANGED_FILES";
+const STAGED_VAR: &str = "ZTN_STAGED_FILES";
+
+/// Files changed relative to `base` (committed on this branch since it
+/// forked, plus anything uncommitted), or just the uncommitted ones without
+/// a base. Paths are relative to the project root, which must be the cwd.
+pub fn changed_files(base: Option<&str>) -> Result<Vec<String>> {
+    let mut files = BTreeSet::new();
+    if let Some(base) = base {
+        files.extend(git(&["diff", "--name-only", "--relative", &format!("{}...HEAD", base)])?);
+    }
+    files.extend(git(&["diff", "--name-only", "--relative", "HEAD"])?);
+    files.extend(git(&["ls-files", "--others", "--exclude-standard"])?);
+    Ok(files.into_iter().collect())
+}
+
+/// Tasks whose `inputs` match any of `changed`, plus everything that
+/// (transitively) depends on them.
+pub fn affected_tasks(config: &Config, changed: &[String]) -> Result<HashSet<String>> {
+    let mut affected = HashSet::new();
+    for (name, task) in &config.tasks {
+        if task.inputs.is_empty() {
+            continue;
+        }
+        let globs = build_glob_set(&task.inputs)?;
+        if changed.iter().any(|f| globs.is_match(f)) {
+            affected.insert(name.clone());
+        }
+    }
+
+    // Walk up to dependents until nothing new turns up
+    let mut grew = true;
+    while grew {
+        grew = false;
+        for (name, task) in &config.tasks {
+            if !affected.contains(name) && task.prerequisites().iter().any(|d| affected.contains(d)) {
+                affected.insert(name.clone());
+                grew = true;
+            }
+        }
+    }
+    Ok(affected)
+}
+
+/// Gives every task that uses `${ZTN_CHANGED_FILES}`, `${ZTN_STAGED_FILES}`
+/// or `pass_files` the touched files its `inputs` match (all of them when
+/// it has none). Deleted files are left out. Outside a git repository the
+/// lists are empty.
+pub fn bind_touched_files(config: &mut Config) -> Result<()> {
+    if !config.tasks.values().any(wants_files) {
+        return Ok(());
+    }
+    let existing = |files: Vec<String>| -> Vec<String> { files.into_iter().filter(|f| Path::new(f).is_file()).collect() };
+    let (changed, staged) = match (changed_files(None), git(&["diff", "--name-only", "--relative", "--cached"])) {
+        (Ok(changed), Ok(staged)) => (existing(changed), existing(staged)),
+        (Err(e), _) | (_, Err(e)) => {
+            crate::log::warn(&format!("Could not list changed files: {}", e));
+            (Vec::new(), Vec::new())
+        }
+    };
+
+    for task in config.tasks.values_mut().filter(|t| wants_files(t)) {
+        let globs = build_glob_set(&task.inputs)?;
+        let relevant = |files: &[String]| -> Vec<String> {
+            files.iter().filter(|f| task.inputs.is_empty() || globs.is_match(f)).cloned().collect()
+        };
+        let (changed, staged) = (relevant(&changed), relevant(&staged));
+        task.bound_vars.insert(CHANGED_VAR.to_string(), quote_all(&changed));
+        task.bound_vars.insert(STAGED_VAR.to_string(), quote_all(&staged));
+        if task.pass_files {
+            task.passed_files = changed;
+        }
+    }
+    Ok(())
+}
+
+fn wants_files(task: &TaskConfig) -> bool {
+    task.pass_files
+        || task
+            .platform_cmd()
+            .is_some_and(|c| c.contains(CHANGED_VAR) || c.contains(STAGED_VAR))
+}
+
+/// Joins paths into shell arguments, quoting the ones that need it.
+pub fn quote_all(files: &[String]) -> String {
+    files.iter().map(|f| quote(f)).collect::<Vec<_>>().join(" ")
+}
+
+fn quote(file: &str) -> String {
+    if file.chars().all(|c| c.is_ascii_alphanumeric() || "._-/+@:,".contains(c)) {
+        file.to_string()
+    } else if cfg!(windows) {
+        format!("\"{}\"", file)
+    } else {
+        format!("'{}'", file.replace('\'', "'\\''"))
+    }
+}
+
+/// Where git looks for hooks, honouring `core.hooksPath` and worktrees.
+pub fn hooks_dir() -> Result<PathBuf> {
+    git(&["rev-parse", "--git-path", "hooks"])?
+        .into_iter()
+        .next()
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("USER_ERROR: Could not locate the git hooks directory"))
+}
+
+/// Runs git and returns its output lines.
+fn git(args: &[&str]) -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| anyhow!("USER_ERROR: Could not run git: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "USER_ERROR: git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect())
+}
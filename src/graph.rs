@@ -9,11 +9,11 @@ pub fn run(config: &Config) -> Result<()> {
     
     // Print a tree for each "root" task (tasks that nothing else depends on)
     let all_deps: HashSet<_> = config.tasks.values()
-        .flat_map(|t| t.depends_on.iter())
+        .flat_map(|t| t.depends_on.iter().map(|d| d.task()))
         .collect();
 
     let mut roots: Vec<_> = config.tasks.keys()
-        .filter(|name| !all_deps.contains(name))
+        .filter(|name| !all_deps.contains(name.as_str()))
         .collect();
     roots.sort();
 
@@ -50,7 +50,7 @@ fn print_tree(
         let count = task.depends_on.len();
         
         for (i, dep) in task.depends_on.iter().enumerate() {
-            print_tree(config, dep, &new_prefix, i == count - 1, visited);
+            print_tree(config, dep.task(), &new_prefix, i == count - 1, visited);
         }
     }
 
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::time::Duration;
+
+const HISTORY_PATH: &str = ".zetten/history.toml";
+
+/// Facts remembered between runs, stored in `.zetten/history.toml`.
+#[derive(Serialize, Deserialize, Default)]
+pub struct History {
+    /// Smoothed wall time of each task's last executions, in milliseconds
+    #[serde(default)]
+    pub durations_ms: BTreeMap<String, u64>,
+    /// What the latest top-level run was asked to do, for `ztn rerun`
+    pub last_run: Option<LastRun>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct LastRun {
+    pub args: Vec<String>,
+    pub vars: BTreeMap<String, String>,
+    pub profile: Option<String>,
+    pub matrix: Vec<(String, String)>,
+    /// Tasks that failed or were cancelled, in plan order
+    pub unfinished: Vec<String>,
+}
+
+impl History {
+    /// Missing or unreadable history is treated as empty.
+    pub fn load() -> Self {
+        fs::read_to_string(HISTORY_PATH)
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(".zetten")?;
+        fs::write(HISTORY_PATH, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Folds freshly measured durations in, weighting the newest run at half
+    /// so one noisy run doesn't swing the estimate.
+    pub fn record_durations(&mut self, observed: &HashMap<String, Duration>) {
+        for (name, dur) in observed {
+            let new_ms = dur.as_millis() as u64;
+            let entry = self.durations_ms.entry(name.clone()).or_insert(new_ms);
+            *entry = (*entry + new_ms) / 2;
+        }
+    }
+
+    /// Estimated duration for each of `names`. Tasks never measured get the
+    /// mean of the known ones (or one second) so chain depth still counts.
+    pub fn estimates(&self, names: &[String]) -> HashMap<String, Duration> {
+        let known: Vec<u64> = names.iter().filter_map(|n| self.durations_ms.get(n).copied()).collect();
+        let fallback = if known.is_empty() {
+            1000
+        } else {
+            known.iter().sum::<u64>() / known.len() as u64
+        };
+        names
+            .iter()
+            .map(|n| {
+                let ms = self.durations_ms.get(n).copied().unwrap_or(fallback);
+                (n.clone(), Duration::from_millis(ms))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,168 @@
+//! Git hook shims (`ztn hooks`).
+//!
+//! A shim is a small shell script in the repository's hooks directory that
+//! calls `ztn hooks run <hook>`, which runs the tasks listing that hook in
+//! `hooks`. A hook that was already there is moved aside to
+//! `<hook>.pre-ztn` and keeps running first.
+//!
+//! Tasks see what git handed the hook through `ZTN_HOOK_ARGS` (its
+//! arguments, shell-quoted) and `ZTN_HOOK_STDIN` (its input, for the hooks
+//! that get any).
+
+use crate::config::Config;
+use crate::git;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Client-side hooks a task may list.
+pub const SUPPORTED: &[&str] = &[
+    "pre-commit",
+    "pre-merge-commit",
+    "prepare-commit-msg",
+    "commit-msg",
+    "post-commit",
+    "post-checkout",
+    "post-merge",
+    "post-rewrite",
+    "pre-rebase",
+    "pre-push",
+];
+
+/// Hooks git feeds on stdin; their shims buffer it so both the previous
+/// hook and ztn get a copy.
+const READS_STDIN: &[&str] = &["pre-push", "post-rewrite"];
+
+const MARKER: &str = "# Installed by zetten (ztn hooks install)";
+const BACKUP_SUFFIX: &str = ".pre-ztn";
+
+/// Installs shims for `requested`, or for every hook some task lists.
+pub fn install(config: &Config, requested: &[String]) -> Result<()> {
+    let hooks: BTreeSet<String> = if requested.is_empty() {
+        config.tasks.values().flat_map(|t| t.hooks.iter().cloned()).collect()
+    } else {
+        if let Some(unknown) = requested.iter().find(|h| !SUPPORTED.contains(&h.as_str())) {
+            return Err(anyhow!("USER_ERROR: '{}' is not a supported git hook", unknown));
+        }
+        requested.iter().cloned().collect()
+    };
+    if hooks.is_empty() {
+        return Err(anyhow!("USER_ERROR: No task lists any git hooks (e.g. hooks = [\"pre-commit\"])"));
+    }
+
+    let dir = git::hooks_dir()?;
+    fs::create_dir_all(&dir)?;
+    let exe = std::env::current_exe()?.to_string_lossy().replace('\\', "/");
+    for hook in &hooks {
+        let path = dir.join(hook);
+        if path.exists() && !is_shim(&path) {
+            let backup = backup_path(&path);
+            if backup.exists() {
+                return Err(anyhow!(
+                    "USER_ERROR: Both {} and {} exist; remove one before installing",
+                    path.display(),
+                    backup.display()
+                ));
+            }
+            fs::rename(&path, &backup)?;
+            crate::log::info(&format!("Existing {} hook kept as {}", hook, backup.display()));
+        }
+        fs::write(&path, shim(hook, &exe))?;
+        make_executable(&path)?;
+        crate::log::info(&format!("Installed {} hook", hook));
+    }
+    Ok(())
+}
+
+/// Removes every shim and puts back the hooks they replaced.
+pub fn uninstall() -> Result<()> {
+    let dir = git::hooks_dir()?;
+    let mut removed = 0;
+    for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if !is_shim(&path) {
+            continue;
+        }
+        fs::remove_file(&path)?;
+        let backup = backup_path(&path);
+        if backup.exists() {
+            fs::rename(&backup, &path)?;
+        }
+        crate::log::info(&format!("Removed {} hook", entry.file_name().to_string_lossy()));
+        removed += 1;
+    }
+    if removed == 0 {
+        crate::log::info("No ztn hooks are installed.");
+    }
+    Ok(())
+}
+
+/// Exposes the hook's arguments and input to the tasks it runs. Must be
+/// called before any worker thread starts.
+pub fn export_context(hook: &str, args: &[String]) -> Result<()> {
+    let mut input = String::new();
+    if READS_STDIN.contains(&hook) {
+        std::io::stdin().read_to_string(&mut input)?;
+    }
+    std::env::set_var("ZTN_HOOK_ARGS", git::quote_all(args));
+    std::env::set_var("ZTN_HOOK_STDIN", input.trim_end_matches('\n'));
+    Ok(())
+}
+
+/// Tasks that run for `hook`, sorted by name.
+pub fn tasks_for<'a>(config: &'a Config, hook: &str) -> Vec<&'a str> {
+    let mut names: Vec<&str> = config
+        .tasks
+        .iter()
+        .filter(|(_, t)| !t.private && t.hooks.iter().any(|h| h == hook))
+        .map(|(n, _)| n.as_str())
+        .collect();
+    names.sort();
+    names
+}
+
+fn shim(hook: &str, exe: &str) -> String {
+    let (buffer, feed) = if READS_STDIN.contains(&hook) {
+        // The trailing `.` keeps the input's final newline from being stripped
+        ("input=$(cat; echo .)\ninput=${input%.}\n", r#"printf '%s' "$input" | "#)
+    } else {
+        ("", "")
+    };
+    format!(
+        r#"#!/bin/sh
+{MARKER}; remove with `ztn hooks uninstall`
+{buffer}previous="$0{BACKUP_SUFFIX}"
+if [ -x "$previous" ]; then
+    {feed}"$previous" "$@" || exit $?
+fi
+ztn="{exe}"
+[ -x "$ztn" ] || ztn=ztn
+{feed}exec "$ztn" hooks run {hook} -- "$@"
+"#
+    )
+}
+
+fn is_shim(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|s| s.contains(MARKER))
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(BACKUP_SUFFIX);
+    path.with_file_name(name)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    // Git for Windows runs hooks through its own sh regardless of mode bits
+    Ok(())
+}
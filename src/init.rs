@@ -6,14 +6,6 @@ use crate::templates::{self, TEMPLATES};
 use crate::errors::ZettenError;
 use colored::*;
 
-fn detect_env() -> Vec<&'static str> {
-    let mut env = Vec::new();
-    if Path::new(".venv").exists() { env.push("venv"); }
-    if Path::new("requirements.txt").exists() { env.push("pip"); }
-    if Path::new("pyproject.toml").exists() { env.push("pyproject"); }
-    env
-}
-
 fn detect_template() -> &'static str {
     if Path::new("manage.py").exists() { return "django"; }
     if let Ok(content) = fs::read_to_string("pyproject.toml") {
@@ -47,9 +39,7 @@ pub fn init(template: &str) -> Result<()> {
         .unwrap_or(templates::DEFAULT);
 
     let py_path = Path::new("pyproject.toml");
-    let target_file;
-    
-    if py_path.exists() {
+    let target_file = if py_path.exists() {
         let existing = fs::read_to_string(py_path).map_err(ZettenError::IoError)?;
         if existing.contains("[tool.zetten]") {
             return Err(ZettenError::AlreadyInitialized.into());
@@ -58,11 +48,11 @@ pub fn init(template: &str) -> Result<()> {
         let formatted = templates::format_for_pyproject(content.trim_start());
         let mut file = fs::OpenOptions::new().append(true).open(py_path).map_err(ZettenError::IoError)?;
         writeln!(file, "\n{}", formatted).map_err(ZettenError::IoError)?;
-        target_file = "pyproject.toml";
+        "pyproject.toml"
     } else {
         fs::write("zetten.toml", content.trim_start()).map_err(ZettenError::IoError)?;
-        target_file = "zetten.toml";
-    }
+        "zetten.toml"
+    };
 
     println!("\n{}:", "🎉 Zetten Initialized Successfully".green().bold());
     println!("   Added configuration to {}", target_file.yellow());
@@ -0,0 +1,186 @@
+//! GNU make jobserver support (POSIX pipe and fifo protocols).
+//!
+//! Under `make -jN`, ztn is a client: every task beyond the first must hold
+//! a token read from make's pipe. Otherwise ztn creates its own jobserver
+//! sized to `--workers` and exports it through `MAKEFLAGS`, so nested
+//! `make`, `cargo` or `ztn` draw from the same budget.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub struct Jobserver {
+    #[cfg(unix)]
+    read: std::fs::File,
+    #[cfg(unix)]
+    write: std::fs::File,
+    implicit_taken: Mutex<bool>,
+    /// Held while one task gathers its slots, so two tasks never each sit
+    /// on part of what the other needs
+    gathering: Mutex<()>,
+    /// Total slots, implicit one included; a client that can't tell assumes 1
+    size: u32,
+    /// Value for `--jobserver-auth` when we are the server
+    exported: Option<(usize, String)>,
+}
+
+/// A held job slot; dropping it hands the slot back.
+pub struct Token<'a> {
+    server: &'a Jobserver,
+    byte: Option<u8>, // None = the implicit slot every client owns
+}
+
+impl Drop for Token<'_> {
+    fn drop(&mut self) {
+        match self.byte {
+            Some(b) => self.server.put_back(b),
+            None => *self.server.implicit_taken.lock().unwrap() = false,
+        }
+    }
+}
+
+impl Jobserver {
+    /// Blocks until `count` slots are held (at least one, at most all of
+    /// them: asking for more than exist would never return).
+    pub fn acquire(&self, count: u32) -> Result<Vec<Token<'_>>> {
+        let _turn = self.gathering.lock().unwrap();
+        let mut tokens = Vec::new();
+        for _ in 0..count.clamp(1, self.size.max(1)) {
+            let implicit = {
+                let mut taken = self.implicit_taken.lock().unwrap();
+                !std::mem::replace(&mut *taken, true)
+            };
+            let byte = if implicit { None } else { Some(self.take()?) };
+            tokens.push(Token { server: self, byte });
+        }
+        Ok(tokens)
+    }
+
+    /// Variables that point child processes at this jobserver. Empty when we
+    /// are only a client: children then inherit the parent make's MAKEFLAGS.
+    pub fn child_env(&self) -> HashMap<String, String> {
+        let mut env = HashMap::new();
+        if let Some((jobs, auth)) = &self.exported {
+            let flags = format!(" -j{} --jobserver-fds={} --jobserver-auth={}", jobs, auth, auth);
+            env.insert("MAKEFLAGS".to_string(), flags.clone());
+            env.insert("CARGO_MAKEFLAGS".to_string(), flags);
+        }
+        env
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::Jobserver;
+    use anyhow::{anyhow, Result};
+    use std::fs::{File, OpenOptions};
+    use std::io::{ErrorKind, Read, Write};
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::sync::Mutex;
+
+    impl Jobserver {
+        /// Connects to the jobserver advertised in `MAKEFLAGS`, if any.
+        pub fn from_env() -> Option<Self> {
+            let flags = std::env::var("MAKEFLAGS").ok()?;
+            let auth = flags
+                .split_whitespace()
+                .filter_map(|f| f.strip_prefix("--jobserver-auth=").or_else(|| f.strip_prefix("--jobserver-fds=")))
+                .next_back()?;
+
+            let size = flags
+                .split_whitespace()
+                .filter_map(|f| f.strip_prefix("-j")?.parse().ok())
+                .next_back()
+                .unwrap_or(1);
+
+            let (read, write) = if let Some(path) = auth.strip_prefix("fifo:") {
+                let fifo = OpenOptions::new().read(true).write(true).open(path).ok()?;
+                (fifo.try_clone().ok()?, fifo)
+            } else {
+                let (r, w) = auth.split_once(',')?;
+                (dup_fd(r.parse().ok()?)?, dup_fd(w.parse().ok()?)?)
+            };
+
+            Some(Self {
+                read,
+                write,
+                implicit_taken: Mutex::new(false),
+                gathering: Mutex::new(()),
+                size,
+                exported: None,
+            })
+        }
+
+        /// Creates a jobserver holding `slots` slots in total.
+        pub fn create(slots: usize) -> Result<Self> {
+            let mut fds = [0; 2];
+            // Deliberately inheritable: task processes must see both ends
+            if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+                return Err(anyhow!("Failed to create jobserver pipe: {}", std::io::Error::last_os_error()));
+            }
+            let read = unsafe { File::from_raw_fd(fds[0]) };
+            let mut write = unsafe { File::from_raw_fd(fds[1]) };
+            // One slot is implicit, the rest are bytes in the pipe
+            write.write_all(&vec![b'+'; slots.saturating_sub(1)])?;
+
+            Ok(Self {
+                read,
+                write,
+                implicit_taken: Mutex::new(false),
+                gathering: Mutex::new(()),
+                size: slots as u32,
+                exported: Some((slots, format!("{},{}", fds[0], fds[1]))),
+            })
+        }
+
+        pub(super) fn take(&self) -> Result<u8> {
+            let mut byte = [0u8; 1];
+            loop {
+                match (&self.read).read(&mut byte) {
+                    Ok(1) => return Ok(byte[0]),
+                    Ok(_) => return Err(anyhow!("Jobserver pipe closed")),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    // make may hand out a non-blocking pipe; wait until a token shows up
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        let mut pfd = libc::pollfd { fd: self.read.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+                        unsafe { libc::poll(&mut pfd, 1, -1) };
+                    }
+                    Err(e) => return Err(anyhow!("Failed to read jobserver token: {}", e)),
+                }
+            }
+        }
+
+        pub(super) fn put_back(&self, byte: u8) {
+            let _ = (&self.write).write_all(&[byte]);
+        }
+    }
+
+    /// Duplicates an inherited descriptor so dropping ours never closes make's.
+    fn dup_fd(fd: i32) -> Option<File> {
+        let copy = unsafe { libc::dup(fd) };
+        (copy >= 0).then(|| unsafe { File::from_raw_fd(copy) })
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::Jobserver;
+    use anyhow::{anyhow, Result};
+
+    // Windows jobservers use named semaphores, which are not supported yet.
+    impl Jobserver {
+        pub fn from_env() -> Option<Self> {
+            None
+        }
+
+        pub fn create(_slots: usize) -> Result<Self> {
+            Err(anyhow!("Jobserver is not supported on this platform"))
+        }
+
+        pub(super) fn take(&self) -> Result<u8> {
+            Err(anyhow!("Jobserver is not supported on this platform"))
+        }
+
+        pub(super) fn put_back(&self, _byte: u8) {}
+    }
+}
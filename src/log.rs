@@ -17,6 +17,18 @@ pub fn task_ok(name: &str, cached: bool) {
     println!("{} {} ({})", "✔".green().bold(), name, status);
 }
 
+pub fn task_skipped(name: &str, reason: &str) {
+    println!("{} {} ({}: {})", "↷".dimmed().bold(), name, "skipped".dimmed(), reason.dimmed());
+}
+
+pub fn task_group(name: &str, breakdown: &str) {
+    println!("{} {} ({}: {})", "✔".green().bold(), name, "group".cyan(), breakdown);
+}
+
+pub fn task_cancelled(name: &str) {
+    println!("{} {} ({})", "⊘".magenta().bold(), name, "cancelled".magenta());
+}
+
 pub fn task_fail(name: &str, code: i32) {
     println!("{} {} (exit code {})", "✗".red().bold(), name, code);
 }
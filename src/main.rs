@@ -1,14 +1,23 @@
 mod cache;
 mod cli;
+mod condition;
 mod config;
 mod doctor;
 mod errors; // New module
+mod git;
 mod graph;
+mod history;
+mod hooks;
+mod jobserver;
 mod init;
 mod log;
 mod progress;
+mod resources;
 mod root;
 mod runner;
+mod service;
+mod session;
+mod tags;
 mod templates;
 mod tui;
 mod validator;
@@ -20,28 +29,39 @@ use crate::progress::Progress;
 use cache::compute_hash;
 use clap::{CommandFactory, Parser};
 use clap_complete::{generate, shells};
-use cli::{Cli, Command};
+use cli::{Cli, Command, HooksAction};
 use colored::*;
 use config::Config;
 use errors::ZettenError; // Import
 use lazy_static::lazy_static;
 use miette::IntoDiagnostic; // Import
 use runner::{execute_task_command, ExecutionResult};
-use std::process::Child;
+use std::io::IsTerminal;
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     env, fs,
     path::Path,
-    sync::{mpsc, Arc, Mutex},
+    sync::{atomic::AtomicBool, mpsc, Arc, Mutex},
     thread,
     time::{Duration, Instant},
 };
 use validator::validate_execution_env;
 
 lazy_static! {
-    static ref PROCESS_REGISTRY: Arc<Mutex<Vec<Child>>> = Arc::new(Mutex::new(Vec::new()));
+    /// PIDs of task processes currently running, killed on Ctrl+C or fail-fast
+    static ref PROCESS_REGISTRY: Mutex<HashSet<u32>> = Mutex::new(HashSet::new());
+    /// Teardowns owed by the current run, run on Ctrl+C
+    static ref PENDING_TEARDOWNS: Mutex<HashMap<String, PendingTeardown>> = Mutex::new(HashMap::new());
 }
 
+/// Resolved command and environment of a teardown that has not run yet
+type PendingTeardown = (String, HashMap<String, String>);
+
+/// Set once a run starts tearing down, so no new task process is spawned
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Set by Ctrl+C: the signal handler alone runs the teardowns, then exits
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
 fn main() -> miette::Result<()> {
     // 1. CLI Parsing (Fast Path)
     let cli = match Cli::try_parse() {
@@ -69,32 +89,39 @@ fn main() -> miette::Result<()> {
     };
 
     // 2. Pillar 2: Graceful Shutdown (Only for actual execution)
-    ctrlc::set_handler(move || {
-        println!(
-            "\n{}",
-            "🛑 Shutdown signal received! Cleaning up processes..."
-                .red()
-                .bold()
-        );
-        if let Ok(mut registry) = PROCESS_REGISTRY.lock() {
-            for mut child in registry.drain(..) {
-                let _ = child.kill();
-            }
-        }
-        println!("{}", "✔ Cleanup complete. Exiting.".yellow());
-        std::process::exit(130);
-    }).into_diagnostic()?;
+    // A nested run shares the parent's process group; the parent cleans up for both
+    if !session::is_nested() {
+        ctrlc::set_handler(move || {
+            println!(
+                "\n{}",
+                "🛑 Shutdown signal received! Cleaning up processes..."
+                    .red()
+                    .bold()
+            );
+            runner::interrupt();
+            run_pending_teardowns();
+            session::cleanup();
+            println!("{}", "✔ Cleanup complete. Exiting.".yellow());
+            std::process::exit(130);
+        }).into_diagnostic()?;
+    }
 
     if Path::new(".env").exists() && dotenvy::dotenv().is_ok() {
         crate::log::info("Environment variables loaded from .env");
     }
 
-    if let Err(e) = run_main(cli) {
-        // Miette will handle the printing nicely
-        return Err(e);
-    }
+    // Miette will handle the printing nicely
+    run_main(cli)
+}
 
-    Ok(())
+/// Runs the teardowns of tasks that were interrupted, in the foreground.
+fn run_pending_teardowns() {
+    let pending = std::mem::take(&mut *PENDING_TEARDOWNS.lock().unwrap());
+    for (name, (cmd, env)) in pending {
+        println!("{}", format!("↺ Running teardown '{}'...", name).yellow());
+        // Bypasses the process registry, which refuses new processes while cancelling
+        let _ = runner::shell_command(&cmd).envs(&env).status();
+    }
 }
 
 fn load_config_safe() -> Option<Config> {
@@ -110,8 +137,7 @@ fn show_usage_guide(config: Option<&Config>) {
     println!("Usage: {} {}", "ztn".green(), "[COMMAND]".cyan());
     if let Some(cfg) = config {
         println!("\n{}", "Detected Tasks:".bold());
-        let mut tasks: Vec<_> = cfg.tasks.keys().collect();
-        tasks.sort();
+        let tasks = cfg.visible_tasks();
         for t in tasks.iter().take(5) {
             println!("  - {: <12} {}", t.yellow(), cfg.tasks[*t].description);
         }
@@ -125,14 +151,21 @@ pub fn run_main(cli: Cli) -> miette::Result<()> {
     };
 
     match command {
-        Command::Tasks => {
+        Command::Tasks { tag } => {
             let (root, source) = root::find_project_root().map_err(|_| ZettenError::ConfigMissing)?;
             env::set_current_dir(&root).into_diagnostic()?;
-            let config = Config::load(&source).map_err(|e| ZettenError::Anyhow(e))?;
-            let mut keys: Vec<_> = config.tasks.keys().collect();
-            keys.sort();
-            for name in keys {
-                println!("  {:<15} {}", name, config.tasks[name].description);
+            let config = Config::load(&source).map_err(ZettenError::Anyhow)?;
+            let expr = tag.as_deref().map(tags::TagExpr::parse).transpose()?;
+            for name in config.visible_tasks() {
+                let task = &config.tasks[name];
+                if expr.as_ref().is_some_and(|e| !e.matches(&task.selection_tags())) {
+                    continue;
+                }
+                if task.aliases.is_empty() {
+                    println!("  {:<15} {}", name, task.description);
+                } else {
+                    println!("  {:<15} {} (aliases: {})", name, task.description, task.aliases.join(", "));
+                }
             }
             Ok(())
         }
@@ -143,26 +176,95 @@ pub fn run_main(cli: Cli) -> miette::Result<()> {
             kv,
             args,
             tag,
+            profile,
+            yes,
+            keep_going,
+            matrix,
+            failed,
+            only,
+            from,
+            until,
+            affected,
+            base,
+            matches,
+            allow_empty,
         } => {
-            if tasks.is_empty() && tag.is_none() {
+            if tasks.is_empty() && tag.is_none() && matches.is_empty() && !failed && !affected {
                 tui::show_selector().map_err(|e| miette::Report::new(ZettenError::Anyhow(e)))
             } else {
-                // Convert CLI Vec to HashMap for the merger
-                let cli_vars: HashMap<String, String> = kv.into_iter().collect();
-                let exit_code = run_tasks(tasks, workers, dry_run, args, tag, cli_vars)?;
+                let exit_code = run_tasks(RunOptions {
+                    tasks,
+                    workers,
+                    dry_run,
+                    args,
+                    tag_filter: tag,
+                    // Convert CLI Vec to HashMap for the merger
+                    cli_vars: kv.into_iter().collect(),
+                    profile: profile.or_else(|| env::var("ZTN_PROFILE").ok()),
+                    assume_yes: yes,
+                    keep_going,
+                    matrix_filter: matrix,
+                    failed_only: failed,
+                    only,
+                    from,
+                    until,
+                    affected,
+                    base,
+                    patterns: matches,
+                    allow_empty,
+                })?;
                 if exit_code != 0 {
                     std::process::exit(exit_code);
                 }
                 Ok(())
             }
         }
+        Command::Rerun { workers, keep_going } => {
+            let exit_code = run_tasks(RunOptions {
+                workers,
+                keep_going,
+                failed_only: true,
+                ..Default::default()
+            })?;
+            if exit_code != 0 {
+                std::process::exit(exit_code);
+            }
+            Ok(())
+        }
+        Command::Hooks { action } => {
+            let (root, source) = root::find_project_root().map_err(|_| ZettenError::ConfigMissing)?;
+            env::set_current_dir(&root).into_diagnostic()?;
+            let config = Config::load(&source).map_err(ZettenError::Anyhow)?;
+            match action {
+                HooksAction::Install { hooks } => hooks::install(&config, &hooks).map_err(ZettenError::Anyhow)?,
+                HooksAction::Uninstall => hooks::uninstall().map_err(ZettenError::Anyhow)?,
+                HooksAction::Run { hook, args } => {
+                    if hooks::tasks_for(&config, &hook).is_empty() {
+                        return Ok(());
+                    }
+                    hooks::export_context(&hook, &args).map_err(ZettenError::Anyhow)?;
+                    let exit_code = run_tasks(RunOptions {
+                        tag_filter: Some(hook),
+                        ..Default::default()
+                    })?;
+                    if exit_code != 0 {
+                        std::process::exit(exit_code);
+                    }
+                }
+            }
+            Ok(())
+        }
         Command::Watch { tasks } => {
             let (root, source) = root::find_project_root().map_err(|_| ZettenError::ConfigMissing)?;
             env::set_current_dir(&root).into_diagnostic()?;
-            let config = Config::load(&source).map_err(|e| ZettenError::Anyhow(e))?;
+            let config = Config::load(&source).map_err(ZettenError::Anyhow)?;
             if tasks.is_empty() {
                 return Err(ZettenError::TaskNotFound("No tasks specified".to_string()).into());
             }
+            let tasks: Vec<String> = tasks
+                .iter()
+                .map(|t| config.resolve_name(t).unwrap_or(t).to_string())
+                .collect();
             watch::run(&config, &tasks).map_err(|e| miette::Report::new(ZettenError::Anyhow(e)))?;
             Ok(())
         }
@@ -186,46 +288,189 @@ pub fn run_main(cli: Cli) -> miette::Result<()> {
     }
 }
 
-pub(crate) fn run_tasks(
-    tasks: Vec<String>,
-    workers: String,
-    dry_run: bool,
-    args: Vec<String>,
-    tag_filter: Option<String>,
-    cli_vars: HashMap<String, String>, // Added
-) -> Result<i32, ZettenError> { // Return explicit ZettenError Result
+/// Everything a single `ztn run` invocation needs; callers outside the CLI
+/// (TUI selector, watch mode) fill in what they need and default the rest.
+pub(crate) struct RunOptions {
+    pub tasks: Vec<String>,
+    pub workers: String,
+    pub dry_run: bool,
+    pub args: Vec<String>,
+    pub tag_filter: Option<String>,
+    pub cli_vars: HashMap<String, String>,
+    pub profile: Option<String>,
+    pub assume_yes: bool, // Answer `confirm` prompts with yes
+    pub keep_going: bool, // Run everything not downstream of a failure
+    pub matrix_filter: Vec<(String, String)>, // --matrix axis=value selections
+    pub failed_only: bool, // Re-run the failures of the last run with its options
+    pub only: bool, // Don't pull in depends_on
+    pub from: Option<String>, // Drop the part of the plan ordered before this task
+    pub until: Option<String>, // Drop the part of the plan ordered after this task
+    pub affected: bool, // Keep only tasks touched by the git changes
+    pub base: Option<String>, // Ref that --affected diffs against
+    pub patterns: Vec<String>, // --match regexes over task names
+    pub allow_empty: bool, // A pattern matching nothing is not an error
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            tasks: Vec::new(),
+            workers: "auto".to_string(),
+            dry_run: false,
+            args: Vec::new(),
+            tag_filter: None,
+            cli_vars: HashMap::new(),
+            profile: env::var("ZTN_PROFILE").ok(),
+            assume_yes: false,
+            keep_going: false,
+            matrix_filter: Vec::new(),
+            failed_only: false,
+            only: false,
+            from: None,
+            until: None,
+            affected: false,
+            base: None,
+            patterns: Vec::new(),
+            allow_empty: false,
+        }
+    }
+}
+
+pub(crate) fn run_tasks(opts: RunOptions) -> Result<i32, ZettenError> { // Return explicit ZettenError Result
+    let RunOptions {
+        mut tasks,
+        workers,
+        dry_run,
+        mut args,
+        mut tag_filter,
+        mut cli_vars,
+        mut profile,
+        assume_yes,
+        keep_going,
+        mut matrix_filter,
+        failed_only,
+        only,
+        from,
+        until,
+        affected,
+        base,
+        mut patterns,
+        allow_empty,
+    } = opts;
+
     let (root, source) =
         root::find_project_root().map_err(|_| ZettenError::ConfigMissing)?;
     env::set_current_dir(&root).map_err(ZettenError::IoError)?; // std::io::Error -> ZettenError
 
-    let config = Arc::new(Config::load(&source).map_err(|e| ZettenError::Anyhow(e))?);
-    config.validate().map_err(|e| ZettenError::Anyhow(e))?;
+    // --failed replays the previous run's options on just what didn't succeed
+    if failed_only {
+        let Some(last) = history::History::load().last_run.filter(|l| !l.unfinished.is_empty()) else {
+            crate::log::info("Nothing to re-run: the last run had no failures.");
+            return Ok(0);
+        };
+        crate::log::info(&format!("Re-running {} task(s) from the last run", last.unfinished.len()));
+        tasks = last.unfinished;
+        args = last.args;
+        cli_vars = last.vars.into_iter().collect();
+        profile = last.profile;
+        matrix_filter = last.matrix;
+        tag_filter = None;
+        patterns.clear();
+    }
+    let last_run_options = history::LastRun {
+        args: args.clone(),
+        vars: cli_vars.clone().into_iter().collect(),
+        profile: profile.clone(),
+        matrix: matrix_filter.clone(),
+        unfinished: Vec::new(),
+    };
+
+    let mut config = Config::load(&source).map_err(ZettenError::Anyhow)?;
+    let profile = match profile {
+        Some(name) => {
+            crate::log::info(&format!("Using profile '{}'", name));
+            config.apply_profile(&name).map_err(ZettenError::Anyhow)?
+        }
+        None => Default::default(),
+    };
+    config.validate().map_err(ZettenError::Anyhow)?;
+    config.instantiate_dependencies();
+    config.expand_matrices(&matrix_filter);
+    git::bind_touched_files(&mut config).map_err(ZettenError::Anyhow)?;
+    let config = Arc::new(config);
 
-    // --- NEW: THREE-TIER VARIABLE MERGE ---
+    // --- NEW: THREE-TIER VARIABLE MERGE (plus the active profile) ---
     let mut all_vars: HashMap<String, String> = HashMap::new();
     for (k, v) in env::vars() {
         all_vars.insert(k, v);
     } // Tier 3
+    for (k, v) in &profile.env {
+        all_vars.insert(k.clone(), v.clone());
+    } // Tier 3 (profile env shadows the shell)
     for (k, v) in &config.vars {
         all_vars.insert(k.clone(), v.clone());
     } // Tier 2
+    for (k, v) in &profile.vars {
+        all_vars.insert(k.clone(), v.clone());
+    } // Tier 2 (profile vars shadow [vars])
     for (k, v) in cli_vars {
         all_vars.insert(k, v);
     } // Tier 1 (Winner)
     let all_vars = Arc::new(all_vars);
+    let mut task_env = profile.env;
 
-    let mut root_tasks = tasks;
+    let selecting = !patterns.is_empty() || tasks.iter().any(|t| is_glob(t));
+    let mut root_tasks = select_by_pattern(&config, tasks, &patterns, allow_empty)?;
     if let Some(ref t) = tag_filter {
+        let expr = tags::TagExpr::parse(t)?;
         let tagged: Vec<String> = config
             .tasks
             .iter()
-            .filter(|(_, c)| matches_tag_expression(t, &c.tags))
+            .filter(|(_, c)| !c.private && expr.matches(&c.selection_tags()))
             .map(|(n, _): (&String, _)| n.clone())
             .collect();
         root_tasks.extend(tagged);
     }
 
-    let task_names = collect_tasks(&config, &root_tasks)?;
+    if selecting && root_tasks.is_empty() {
+        crate::log::info("No tasks match the given patterns.");
+        return Ok(0);
+    }
+
+    // --affected with no tasks named considers every public one
+    if affected && root_tasks.is_empty() {
+        root_tasks = config.public_names().into_iter().map(String::from).collect();
+    }
+
+    let mut task_names = collect_tasks(&config, &root_tasks, failed_only, !only)?;
+    // --affected then narrows the whole plan, dependencies included, to the
+    // tasks whose inputs the git changes touch (or that depend on one)
+    if affected {
+        let changed = git::changed_files(base.as_deref()).map_err(ZettenError::Anyhow)?;
+        let hit = git::affected_tasks(&config, &changed).map_err(ZettenError::Anyhow)?;
+        let kept = task_names.iter().map(String::as_str).filter(|n| hit.contains(*n)).collect();
+        task_names = keep_with_lifecycle(&config, &task_names, kept);
+        if task_names.is_empty() {
+            crate::log::info(&format!("No tasks affected by {} changed file(s).", changed.len()));
+            return Ok(0);
+        }
+    }
+    // An axis no planned matrix has is a typo, not a filter (a re-run may
+    // legitimately replay one that only its finished tasks had)
+    if !failed_only {
+        if let Some((axis, _)) = matrix_filter.iter().find(|(axis, _)| {
+            let key = format!("matrix.{}", axis);
+            !task_names.iter().any(|n| config.tasks[n].bound_vars.contains_key(&key))
+        }) {
+            return Err(ZettenError::Anyhow(anyhow::anyhow!(
+                "USER_ERROR: No task in this run has a matrix axis '{}'",
+                axis
+            )));
+        }
+    }
+    let task_names = slice_plan(&config, task_names, from.as_deref(), until.as_deref())?;
+    let session = Arc::new(session::Session::attach(&root)?);
+    session.check_recursion(&task_names)?;
     crate::log::info("🔍 Validating environment...");
     if let Err(e) = validate_execution_env(&config, &task_names) {
         crate::log::user_error(&format!("Validation failed: {}", e));
@@ -235,95 +480,192 @@ pub(crate) fn run_tasks(
     if dry_run {
         crate::log::info("🌵 Dry Run Plan:");
         for n in &task_names {
-            println!(
-                "  [{}] {}",
-                n,
-                config.tasks[n].resolve_cmd(&args, &all_vars)
-            );
+            let task = &config.tasks[n];
+            if task.is_group() {
+                println!("  [{}] (group: {})", n, task.prerequisites().join(", "));
+                continue;
+            }
+            let mut cmd = task.resolve_cmd(&args, &all_vars);
+            if task.pass_files {
+                cmd = format!("{} {}", cmd, git::quote_all(&task.passed_files));
+            }
+            println!("  [{}] {}", n, cmd);
         }
         return Ok(0);
     }
 
-    let workers_count = if workers == "auto" {
+    // Dangerous tasks need an explicit --yes when nobody can answer a prompt
+    if !assume_yes && !std::io::stdin().is_terminal() {
+        if let Some(n) = task_names.iter().find(|n| config.tasks[*n].confirm.is_some()) {
+            return Err(ZettenError::ConfirmationRequired(n.clone()));
+        }
+    }
+
+    // Slot budget shared by all tasks (a task of weight N takes N slots)
+    let workers_count: usize = if workers == "auto" {
         num_cpus::get()
     } else {
-        workers.parse().map_err(|_| ZettenError::TaskFailed("Invalid worker count".to_string(), 1))? // Reuse existing error or just create one. Actually ParseIntError. 
-        // Simplest: .map_err(|e| ZettenError::Anyhow(anyhow::anyhow!(e)))?
+        workers
+            .parse()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ZettenError::TaskFailed("Invalid worker count".to_string(), 1))?
     };
     let is_parallel = task_names.len() > 1 && workers_count > 1;
+    // --workers is a slot budget: tasks draw `weight` slots from it
+    let budget = workers_count as u32;
+
+    // Share the budget with nested make/cargo/ztn: join make's jobserver if we
+    // run under `make -j`, otherwise serve our own through MAKEFLAGS
+    let jobserver = jobserver::Jobserver::from_env()
+        .or_else(|| jobserver::Jobserver::create(workers_count).ok())
+        .map(Arc::new);
+    // What tells this run's work apart from a nested run's (MAKEFLAGS differ by design)
+    let profile_env = Arc::new(task_env.clone());
+    if let Some(js) = &jobserver {
+        task_env.extend(js.child_env());
+    }
+    let task_env = Arc::new(task_env);
     let mut summary = RunSummary::new();
 
 
     // --- Kahn's Algorithm Setup ---
-    let mut indegree: HashMap<String, usize> = HashMap::new();
-    let mut graph_map: HashMap<String, Vec<String>> = HashMap::new();
-    for n in &task_names {
-        indegree.insert(n.clone(), 0usize);
-    }
-    for n in &task_names {
-        for dep in &config.tasks[n].depends_on {
-            if indegree.contains_key(dep.as_str()) {
-                *indegree.get_mut(n.as_str()).unwrap() += 1;
-                graph_map.entry(dep.clone()).or_default().push(n.clone());
-            }
-        }
-    }
+    let mut dag = Dag::new(&config, &task_names);
+    let mut ready: VecDeque<String> = dag.initial();
 
-    let mut ready = VecDeque::new();
-    for (t, &deg) in &indegree {
-        if deg == 0 {
-            ready.push_back(t.clone());
-        }
-    }
+    // Ready tasks start longest-remaining-chain first, using past durations
+    let mut history = history::History::load();
+    let dependents = |n: &str| dag.followers(n);
+    let priority: HashMap<String, Duration> =
+        longest_chains(&task_names, &dependents, &history.estimates(&task_names))
+            .into_iter()
+            .map(|(name, (_, remaining))| (name, remaining))
+            .collect();
+    let plan_pos: HashMap<&str, usize> =
+        task_names.iter().enumerate().map(|(i, n)| (n.as_str(), i)).collect();
+    let mut observed: HashMap<String, Duration> = HashMap::new();
 
     let progress = Arc::new(Progress::new(task_names.len()));
-    let (tx, rx) = mpsc::channel::<anyhow::Result<(String, ExecutionResult, bool)>>(); // explicit anyhow::Result
-    let work_queue = Arc::new(Mutex::new(VecDeque::<String>::new()));
+    // Services must not outlive this function, however it returns
+    let _services = service::StopAllOnDrop;
+    let (tx, rx) = mpsc::channel::<(String, anyhow::Result<(ExecutionResult, TaskOutcome)>)>(); // explicit anyhow::Result
+    runner::reset_cancellation();
+    let (job_tx, job_rx) = mpsc::channel::<String>();
+    let job_rx = Arc::new(Mutex::new(job_rx));
+
+    // Services run in the background only when something in the plan needs them;
+    // on their own they behave like any long-running command
+    let background: Arc<HashSet<String>> = Arc::new(
+        task_names
+            .iter()
+            .filter(|n| config.tasks[*n].service && dag.has_dependents(n))
+            .cloned()
+            .collect(),
+    );
 
-    // Spawn Workers
-    for _ in 0..workers_count {
-        let q = Arc::clone(&work_queue);
+    // Spawn Workers (never more than there are tasks)
+    let mut worker_handles = Vec::new();
+    for _ in 0..workers_count.min(task_names.len()) {
+        let jobs = Arc::clone(&job_rx);
         let t_tx = tx.clone();
         let p = Arc::clone(&progress);
         let cfg = Arc::clone(&config);
         let f_args = args.clone();
         let vars = Arc::clone(&all_vars); // Clone the Arc for the thread
+        let t_env = Arc::clone(&task_env);
+        let p_env = Arc::clone(&profile_env);
+        let js = jobserver.clone();
+        let sess = Arc::clone(&session);
+        let bg = Arc::clone(&background);
 
-        thread::spawn(move || loop {
-            let task_name = {
-                let mut lock = q.lock().unwrap();
-                match lock.pop_front() {
-                    Some(name) => name,
-                    None => {
-                        drop(lock);
-                        thread::sleep(Duration::from_millis(10));
-                        continue;
-                    }
-                }
-            };
+        worker_handles.push(thread::spawn(move || loop {
+            // Block until the scheduler hands over a task; a closed channel means the run is over
+            let next = jobs.lock().unwrap().recv();
+            let Ok(task_name) = next else { break };
+
+            // Queued before fail-fast kicked in; don't start it now
+            if runner::cancellation_requested() {
+                let _ = t_tx.send((task_name, Ok((ExecutionResult::default(), TaskOutcome::Cancelled))));
+                continue;
+            }
 
+            if is_parallel {
+                p.start_task();
+            }
             let task_cfg = cfg.tasks.get(&task_name).unwrap();
             let final_cmd = task_cfg.resolve_cmd(&f_args, &vars); // Resolved with hierarchy
+            // Lets a `ztn run` inside the task find this session
+            let mut t_env = (*t_env).clone();
+            t_env.extend(sess.child_env(&task_name));
 
-            // SETUP PHASE
-            if let Some(setup_task) = &task_cfg.setup {
-                 match execute_task_command(&cfg.tasks[setup_task].resolve_cmd(&f_args, &vars), &[], false, false) {
-                    Ok(r) if !r.is_success => {
-                        let _ = t_tx.send(Ok((task_name.clone(), r, false)));
-                        continue; // Fail early
+            // CONDITIONS (run_if / skip_if): a skipped task still satisfies its dependents
+            let skip = condition::skip_reason(task_cfg, &task_cfg.effective_vars(&vars));
+            if !matches!(skip, Ok(None)) {
+                if is_parallel {
+                    p.finish_task();
+                }
+                let skipped = ExecutionResult {
+                    is_success: true,
+                    ..Default::default()
+                };
+                let _ = t_tx.send((task_name, skip.map(|reason| (skipped, TaskOutcome::Skipped(reason.unwrap_or_default())))));
+                continue;
+            }
+
+            // Nothing to spawn: the dependencies were the work
+            if task_cfg.is_group() {
+                if is_parallel {
+                    p.finish_task();
+                }
+                let done = ExecutionResult {
+                    is_success: true,
+                    ..Default::default()
+                };
+                let _ = t_tx.send((task_name, Ok((done, TaskOutcome::Group))));
+                continue;
+            }
+
+            // Each piece of work runs once per session: if another run of it
+            // (e.g. the one that started us) has it, wait for that instead
+            let work = session::Session::work_key(&task_name, &final_cmd, &p_env);
+            let _claim = match sess.claim(&task_name, &work, runner::cancellation_requested) {
+                Ok(session::Claim::Ours(claim)) => claim,
+                other => {
+                    if is_parallel {
+                        p.finish_task();
                     }
-                    Err(_) => {
-                         // internal error
-                         continue;
+                    let res = match other {
+                        Ok(session::Claim::Done) => Ok((ExecutionResult { is_success: true, ..Default::default() }, TaskOutcome::Cached)),
+                        Ok(_) => Ok((ExecutionResult::default(), TaskOutcome::Cancelled)),
+                        Err(e) => Err(e.into()),
+                    };
+                    let _ = t_tx.send((task_name, res));
+                    continue;
+                }
+            };
+
+            // Hold jobserver slots for as long as the task runs
+            let _slots = match js.as_deref().map(|j| j.acquire(task_cfg.slot_weight(budget))).transpose() {
+                Ok(slots) => slots,
+                Err(e) => {
+                    if is_parallel {
+                        p.finish_task();
                     }
-                    _ => {}
-                 }
-            }
+                    let _ = t_tx.send((task_name, Err(e)));
+                    continue;
+                }
+            };
 
             let interactive = task_cfg.interactive.unwrap_or(false);
-            let res: anyhow::Result<(ExecutionResult, bool)> = (|| { // explicit anyhow
+            let res: anyhow::Result<(ExecutionResult, TaskOutcome)> = (|| { // explicit anyhow
+                // A service someone depends on stays up in the background once ready
+                if bg.contains(&task_name) {
+                    let exec = service::start(&task_name, &final_cmd, &t_env, task_cfg.ready_when.as_ref())?;
+                    return Ok((exec, TaskOutcome::Executed));
+                }
+
                 // ... cache logic ...
-                let cache_path = format!(".zetten/cache/{}.hash", task_name);
+                let cache_path = format!(".zetten/cache/{}.hash", cache::file_key(&task_name));
                 if !task_cfg.inputs.is_empty() && f_args.is_empty() && !interactive {
                     let hash = compute_hash(&task_cfg.inputs)?;
                     if fs::read_to_string(&cache_path)
@@ -335,67 +677,228 @@ pub(crate) fn run_tasks(
                                 is_success: true,
                                 ..Default::default()
                             },
-                            true,
+                            TaskOutcome::Cached,
                         ));
                     }
                 }
                 
-                let exec = execute_task_command(&final_cmd, &task_cfg.allow_exit_codes, is_parallel, interactive)?;
+                let exec = if task_cfg.pass_files {
+                    if task_cfg.passed_files.is_empty() {
+                        let reason = "no changed files match its inputs".to_string();
+                        return Ok((ExecutionResult { is_success: true, ..Default::default() }, TaskOutcome::Skipped(reason)));
+                    }
+                    runner::execute_with_files(&final_cmd, &task_cfg.passed_files, &task_cfg.allow_exit_codes, is_parallel, interactive, &t_env)?
+                } else {
+                    execute_task_command(&final_cmd, &task_cfg.allow_exit_codes, is_parallel, interactive, &t_env)?
+                };
                 
                 if exec.is_success && !interactive && !task_cfg.inputs.is_empty() {
                      let _ = fs::create_dir_all(".zetten/cache");
                      let _ = fs::write(cache_path, compute_hash(&task_cfg.inputs)?);
                 }
-                Ok((exec, false))
+                Ok((exec, TaskOutcome::Executed))
             })();
 
-            // TEARDOWN PHASE
-            if let Some(teardown_task) = &task_cfg.teardown {
-                // Run teardown even if main task failed
-                let _ = execute_task_command(&cfg.tasks[teardown_task].resolve_cmd(&f_args, &vars), &[], false, false);
-            }
-
-
             if is_parallel {
                 p.finish_task();
             }
-            let _ = t_tx.send(res.map(|(e, c)| (task_name, e, c)));
-        });
+            // Marked before the claim is released so no waiter redoes the work
+            if res.as_ref().is_ok_and(|(exec, _)| exec.is_success) {
+                sess.mark_done(&work);
+            }
+            let _ = t_tx.send((task_name, res));
+        }));
     }
 
+    let mut pool = resources::ResourcePool::new(&config.resources, budget);
     let mut in_flight = 0;
-    for t in ready {
-        work_queue.lock().unwrap().push_back(t);
-        in_flight += 1;
+    let mut failures: Vec<(String, i32)> = Vec::new();
+    let mut cancelling = false;
+    // A teardown is only needed once one of its users (or their setups) started
+    let mut started: HashSet<String> = HashSet::new();
+    let mut teardown_users: HashMap<&str, Vec<String>> = HashMap::new();
+    for n in &task_names {
+        if let Some(td) = &config.tasks[n].teardown {
+            let users = teardown_users.entry(td.as_str()).or_default();
+            users.extend(config.tasks[n].setup.clone());
+            users.push(n.clone());
+        }
     }
+    // Failing is fine for a task that planned tasks only list in
+    // `optional_depends_on`; a hard prerequisite still has to succeed
+    let planned = |n: &String| task_names.contains(n);
+    let optional: HashSet<&str> = task_names
+        .iter()
+        .flat_map(|n| config.tasks[n].optional_depends_on.iter().filter(|d| planned(d)))
+        .filter(|d| !task_names.iter().any(|n| config.tasks[n].prerequisites().contains(d)))
+        .map(String::as_str)
+        .collect();
+    let work_key = |name: &str| {
+        session::Session::work_key(name, &config.tasks[name].resolve_cmd(&args, &all_vars), &profile_env)
+    };
+    loop {
+        if runner::interrupted() {
+            // The Ctrl+C handler is running the pending teardowns and will exit
+            // the process; dispatching them here too would run them twice
+            loop {
+                std::thread::park();
+            }
+        }
+
+        // Services are stopped as soon as nothing left in the plan needs them
+        for svc in service::running() {
+            if dag.dependents_settled(&svc) {
+                service::stop(&svc);
+                session.forget(&work_key(&svc));
+            }
+        }
+
+        if cancelling && in_flight == 0 {
+            // Everything fail-fast killed is gone; teardowns may spawn again
+            runner::reset_cancellation();
+        }
+
+        // Dispatch everything that became ready and whose resources are free,
+        // asking first where required
+        let mut waiting = VecDeque::new();
+        ready
+            .make_contiguous()
+            .sort_by(|a, b| priority[b].cmp(&priority[a]).then(plan_pos[a.as_str()].cmp(&plan_pos[b.as_str()])));
+        while let Some(t) = ready.pop_front() {
+            let users = teardown_users.get(t.as_str());
+            let unneeded = users.is_some_and(|u| !u.iter().any(|n| started.contains(n)));
+            if (cancelling && users.is_none()) || unneeded {
+                // Fail-fast starts nothing but teardowns, and only those with something to undo
+                PENDING_TEARDOWNS.lock().unwrap().remove(&t);
+                summary.cancel(&t);
+                settle(&mut dag, &mut ready, &mut summary, &t, false);
+                continue;
+            }
+            if cancelling && in_flight > 0 {
+                waiting.push_back(t);
+                continue;
+            }
+            let claims = config.tasks[&t].resource_claims();
+            let weight = config.tasks[&t].slot_weight(budget);
+            if !pool.try_acquire(&claims, weight) {
+                waiting.push_back(t);
+                continue;
+            }
+            if let Some(message) = config.tasks[&t].confirm.as_deref().filter(|_| !assume_yes) {
+                let approved = if is_parallel {
+                    progress.pb.suspend(|| tui::confirm(message))
+                } else {
+                    tui::confirm(message)
+                };
+                if !approved {
+                    pool.release(&claims, weight);
+                    crate::log::user_error(&format!("Task '{}' was not confirmed.", t));
+                    summary.failed += 1;
+                    summary.outcomes.push((t.clone(), "failed"));
+                    settle(&mut dag, &mut ready, &mut summary, &t, false);
+                    failures.push((t, 1));
+                    if !keep_going {
+                        cancelling = true;
+                        runner::terminate_all();
+                    }
+                    continue;
+                }
+            }
+            // Remember the teardowns this task (or setup) needs so Ctrl+C can still run them
+            for td in teardown_users.iter().filter(|(_, u)| u.contains(&t)).map(|(td, _)| *td) {
+                let mut env = (*task_env).clone();
+                env.extend(session.child_env(td));
+                PENDING_TEARDOWNS
+                    .lock()
+                    .unwrap()
+                    .entry(td.to_string())
+                    .or_insert_with(|| (config.tasks[td].resolve_cmd(&args, &all_vars), env));
+            }
+            started.insert(t.clone());
+            let _ = job_tx.send(t);
+            in_flight += 1;
+        }
+        ready.extend(waiting);
+        if in_flight == 0 {
+            break;
+        }
+
+        let (finished, result) = rx.recv()
+            .map_err(|e| ZettenError::IoError(std::io::Error::other(e)))?;
+        // A task that could not even be started (bad condition, spawn error...)
+        // fails like any other, so teardowns and fail-fast still apply
+        let errored = result.is_err();
+        let (exec, outcome) = result.unwrap_or_else(|e| {
+            let message = format!("Task '{}' could not run: {}", finished, e.to_string().trim_start_matches("USER_ERROR: "));
+            if is_parallel {
+                progress.pb.suspend(|| crate::log::user_error(&message));
+            } else {
+                crate::log::user_error(&message);
+            }
+            let failed = ExecutionResult { exit_code: 1, ..Default::default() };
+            (failed, TaskOutcome::Executed)
+        });
 
-    let mut exit_code = 0;
-    while in_flight > 0 {
-        let (finished, exec, cached) = rx.recv()
-            .map_err(|e| ZettenError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e)))?
-            .map_err(|e| ZettenError::Anyhow(e))?;
-            
         in_flight -= 1;
-        summary.task_metrics.insert(finished.clone(), exec.duration);
         let task_cfg = config.tasks.get(&finished).unwrap();
+        pool.release(&task_cfg.resource_claims(), task_cfg.slot_weight(budget));
+        if teardown_users.contains_key(finished.as_str()) {
+            PENDING_TEARDOWNS.lock().unwrap().remove(&finished);
+        }
+        session.release_orphans(&finished);
+
+        // Anything that stops unsuccessfully after fail-fast kicked in was killed by us
+        // (teardowns only run once the killing is over, so their failures are real)
+        let killed = cancelling && !exec.is_success && !teardown_users.contains_key(finished.as_str());
+        if outcome == TaskOutcome::Cancelled || killed {
+            summary.cancel(&finished);
+            settle(&mut dag, &mut ready, &mut summary, &finished, false);
+            if is_parallel {
+                progress.pb.suspend(|| crate::log::task_cancelled(&finished));
+            } else {
+                crate::log::task_cancelled(&finished);
+            }
+            continue;
+        }
+        summary.task_metrics.insert(finished.clone(), exec.duration);
+        if outcome == TaskOutcome::Executed && !errored {
+            observed.insert(finished.clone(), exec.duration);
+        }
+
+        let nested = session.nested_outcomes(&finished);
+        if !nested.is_empty() {
+            summary.nested.push((finished.clone(), nested));
+        }
 
+        let tolerated = task_cfg.ignore_errors || optional.contains(finished.as_str());
         let mut log_action = || {
-            if exec.is_success || task_cfg.ignore_errors {
+            if outcome == TaskOutcome::Group {
+                // Reported through its members rather than counted itself
+                summary.outcomes.push((finished.clone(), "ok"));
+                crate::log::task_group(&finished, &group_breakdown(&summary.outcomes, &task_cfg.prerequisites()));
+            } else if let TaskOutcome::Skipped(reason) = &outcome {
+                summary.skipped += 1;
+                summary.outcomes.push((finished.clone(), "skipped"));
+                crate::log::task_skipped(&finished, reason);
+            } else if exec.is_success || tolerated {
                 if !exec.is_success {
                     summary.warned += 1;
+                    summary.outcomes.push((finished.clone(), "warned"));
                     crate::log::warn(&format!("Task '{}' failed (ignored).", finished));
-                } else if cached {
+                } else if outcome == TaskOutcome::Cached {
                     summary.cached += 1;
+                    summary.outcomes.push((finished.clone(), "cached"));
                     crate::log::task_ok(&finished, true);
                 } else {
                     summary.succeeded += 1;
+                    summary.outcomes.push((finished.clone(), "ok"));
                     crate::log::task_ok(&finished, false);
                 }
             } else {
                 summary.failed += 1;
-                exit_code = exec.exit_code;
+                summary.outcomes.push((finished.clone(), "failed"));
                 crate::log::task_fail(&finished, exec.exit_code);
-                if is_parallel {
+                if is_parallel || task_cfg.service {
                     if !exec.stdout.is_empty() {
                         println!("{}", String::from_utf8_lossy(&exec.stdout));
                     }
@@ -415,60 +918,241 @@ pub(crate) fn run_tasks(
             log_action();
         }
 
-        if !exec.is_success && !task_cfg.ignore_errors {
-            break;
+        // Dependents of a failed task never become ready
+        let ok = exec.is_success || tolerated;
+        settle(&mut dag, &mut ready, &mut summary, &finished, ok);
+        if exec.is_success {
+            session.mark_done(&work_key(&finished));
         }
-
-        if let Some(children) = graph_map.get(&finished) {
-            for child in children {
-                let deg = indegree.get_mut(child).unwrap();
-                *deg -= 1;
-                if *deg == 0 {
-                    work_queue.lock().unwrap().push_back(child.clone());
-                    in_flight += 1;
-                }
+        if !ok {
+            failures.push((finished, exec.exit_code));
+            if !keep_going && !cancelling {
+                // Fail fast: stop whatever is still running
+                cancelling = true;
+                runner::terminate_all();
             }
         }
     }
+    service::stop_all();
+
+    // Anything still unaccounted for never got to run
+    for name in &task_names {
+        if !summary.outcomes.iter().any(|(n, _)| n == name) {
+            summary.cancel(name);
+        }
+    }
+
+    // Report the failure that comes first in plan order so the exit code is stable
+    let exit_code = failures
+        .iter()
+        .min_by_key(|(name, _)| task_names.iter().position(|n| n == name))
+        .map(|(_, code)| if *code == 0 { 1 } else { *code })
+        .unwrap_or(0);
+
+    history.record_durations(&observed);
+    // Nested runs are part of their parent's run, not a run of their own
+    if !session.is_nested() {
+        let unfinished = task_names
+            .iter()
+            .filter(|n| summary.outcomes.iter().any(|(t, s)| t == *n && matches!(*s, "failed" | "cancelled")))
+            .cloned()
+            .collect();
+        history.last_run = Some(history::LastRun { unfinished, ..last_run_options });
+    }
+    if let Err(e) = history.save() {
+        crate::log::warn(&format!("Could not save run history: {}", e));
+    }
+
+    // Closing the job channel lets idle workers exit; busy ones finish their task first
+    drop(job_tx);
+    for handle in worker_handles {
+        let _ = handle.join();
+    }
 
     if is_parallel {
         progress.pb.finish_and_clear();
     }
-    print_summary(&summary, &config, &task_names);
-    print_summary(&summary, &config, &task_names);
+    if session.is_nested() {
+        // The parent task's run shows our results in its own summary
+        session.report(&summary.flat_outcomes());
+    } else {
+        print_summary(&summary, &config, &task_names);
+    }
     Ok(exit_code)
 }
 
+/// How a finished task got its result.
+#[derive(PartialEq)]
+enum TaskOutcome {
+    Executed,
+    Cached,
+    Skipped(String), // run_if / skip_if reason
+    Cancelled, // Never started because the run was being cancelled
+    Group, // Command-less task whose dependencies all finished
+}
+
 struct RunSummary {
     succeeded: usize,
     cached: usize,
+    skipped: usize,
+    cancelled: usize,
     failed: usize,
     warned: usize,
     start_time: Instant,
     task_metrics: HashMap<String, Duration>,
+    /// Final status of every planned task, in completion order
+    outcomes: Vec<(String, &'static str)>,
+    /// Outcomes reported by nested `ztn run`s, keyed by the task that started them
+    nested: Vec<(String, Vec<(String, String)>)>,
 }
 impl RunSummary {
     fn new() -> Self {
         Self {
             succeeded: 0,
             cached: 0,
+            skipped: 0,
+            cancelled: 0,
             failed: 0,
             warned: 0,
             start_time: Instant::now(),
             task_metrics: HashMap::new(),
+            outcomes: Vec::new(),
+            nested: Vec::new(),
         }
     }
+
+    fn cancel(&mut self, task: &str) {
+        self.cancelled += 1;
+        self.outcomes.push((task.to_string(), "cancelled"));
+    }
+
+    /// Own outcomes followed by those of nested runs, for reporting upwards.
+    fn flat_outcomes(&self) -> Vec<(String, String)> {
+        let own = self.outcomes.iter().map(|(t, status)| (t.clone(), status.to_string()));
+        let nested = self.nested.iter().flat_map(|(_, o)| o.iter().cloned());
+        own.chain(nested).collect()
+    }
 }
 
-fn find_critical_path(
-    config: &Config,
+/// Marks `task` as ended in `dag`, queueing the tasks that became ready and
+/// cancelling the ones it left unable to run.
+fn settle(dag: &mut Dag, ready: &mut VecDeque<String>, summary: &mut RunSummary, task: &str, success: bool) {
+    let (unblocked, blocked) = dag.settle(task, success);
+    ready.extend(unblocked);
+    for b in blocked {
+        summary.cancel(&b);
+    }
+}
+
+/// Kahn's algorithm over the plan. Prerequisites (setup and `depends_on`)
+/// must succeed before a task starts; `after`, `optional_depends_on` and a
+/// teardown only have to finish, however they end.
+struct Dag {
+    indegree: HashMap<String, usize>,
+    dependents: HashMap<String, Vec<String>>,
+    waiters: HashMap<String, Vec<String>>,
+    settled: HashSet<String>,
+}
+
+impl Dag {
+    fn new(config: &Config, task_names: &[String]) -> Self {
+        let mut dag = Dag {
+            indegree: task_names.iter().map(|n| (n.clone(), 0)).collect(),
+            dependents: HashMap::new(),
+            waiters: HashMap::new(),
+            settled: HashSet::new(),
+        };
+        for n in task_names {
+            let task = &config.tasks[n];
+            // Ordering edges only count between tasks that are both planned
+            for dep in task.prerequisites() {
+                if dag.indegree.contains_key(&dep) {
+                    *dag.indegree.get_mut(n).unwrap() += 1;
+                    dag.dependents.entry(dep).or_default().push(n.clone());
+                }
+            }
+            for dep in task.after.iter().chain(&task.optional_depends_on) {
+                if dag.indegree.contains_key(dep) {
+                    *dag.indegree.get_mut(n).unwrap() += 1;
+                    dag.waiters.entry(dep.clone()).or_default().push(n.clone());
+                }
+            }
+            if let Some(td) = task.teardown.as_ref().filter(|td| dag.indegree.contains_key(*td)) {
+                *dag.indegree.get_mut(td).unwrap() += 1;
+                dag.waiters.entry(n.clone()).or_default().push(td.clone());
+            }
+        }
+        dag
+    }
+
+    /// Tasks that can start right away.
+    fn initial(&self) -> VecDeque<String> {
+        self.indegree.iter().filter(|(_, d)| **d == 0).map(|(n, _)| n.clone()).collect()
+    }
+
+    fn has_dependents(&self, task: &str) -> bool {
+        self.dependents.get(task).is_some_and(|d| !d.is_empty())
+    }
+
+    /// Whether every task that needed `task` to succeed has ended.
+    fn dependents_settled(&self, task: &str) -> bool {
+        self.dependents.get(task).into_iter().flatten().all(|d| self.settled.contains(d))
+    }
+
+    /// Tasks that wait on `task` in any way.
+    fn followers(&self, task: &str) -> Vec<String> {
+        let dependents = self.dependents.get(task).into_iter().flatten();
+        dependents.chain(self.waiters.get(task).into_iter().flatten()).cloned().collect()
+    }
+
+    /// Records that `task` ended. Returns the tasks that became ready and,
+    /// when it did not succeed, the dependents that now can never run.
+    fn settle(&mut self, task: &str, success: bool) -> (Vec<String>, Vec<String>) {
+        let (mut ready, mut blocked) = (Vec::new(), Vec::new());
+        self.settled.insert(task.to_string());
+        let mut pending = vec![(task.to_string(), success)];
+        while let Some((t, ok)) = pending.pop() {
+            for child in self.dependents.get(&t).cloned().unwrap_or_default() {
+                if self.settled.contains(&child) {
+                    continue;
+                }
+                if ok {
+                    if self.release(&child) {
+                        ready.push(child);
+                    }
+                } else {
+                    self.settled.insert(child.clone());
+                    blocked.push(child.clone());
+                    pending.push((child, false));
+                }
+            }
+            for waiter in self.waiters.get(&t).cloned().unwrap_or_default() {
+                if !self.settled.contains(&waiter) && self.release(&waiter) {
+                    ready.push(waiter);
+                }
+            }
+        }
+        (ready, blocked)
+    }
+
+    fn release(&mut self, task: &str) -> bool {
+        let deg = self.indegree.get_mut(task).unwrap();
+        *deg -= 1;
+        *deg == 0
+    }
+}
+
+/// For every node reachable from `nodes`, the longest chain (by summed
+/// duration) that starts there and follows `next` edges, in walk order.
+fn longest_chains(
+    nodes: &[String],
+    next: &dyn Fn(&str) -> Vec<String>,
     metrics: &HashMap<String, Duration>,
-    task_names: &[String],
-) -> (Vec<String>, Duration) {
+) -> HashMap<String, (Vec<String>, Duration)> {
     let mut cache: HashMap<String, (Vec<String>, Duration)> = HashMap::new();
     fn compute_path(
         node: &str,
-        config: &Config,
+        next: &dyn Fn(&str) -> Vec<String>,
         metrics: &HashMap<String, Duration>,
         cache: &mut HashMap<String, (Vec<String>, Duration)>,
     ) -> (Vec<String>, Duration) {
@@ -476,30 +1160,42 @@ fn find_critical_path(
             return res.clone();
         }
         let current_dur = *metrics.get(node).unwrap_or(&Duration::ZERO);
-        let mut best_path = vec![node.to_string()];
-        let mut max_dep_dur = Duration::ZERO;
-        if let Some(task_cfg) = config.tasks.get(node) {
-            for dep in &task_cfg.depends_on {
-                let (path, dur) = compute_path(dep, config, metrics, cache);
-                if dur > max_dep_dur {
-                    max_dep_dur = dur;
-                    let mut new_path = path;
-                    new_path.push(node.to_string());
-                    best_path = new_path;
-                }
+        let mut best_tail = Vec::new();
+        let mut max_next_dur = Duration::ZERO;
+        for n in next(node) {
+            let (path, dur) = compute_path(&n, next, metrics, cache);
+            if dur > max_next_dur {
+                max_next_dur = dur;
+                best_tail = path;
             }
         }
-        let res = (best_path, current_dur + max_dep_dur);
+        let mut best_path = vec![node.to_string()];
+        best_path.extend(best_tail);
+        let res = (best_path, current_dur + max_next_dur);
         cache.insert(node.to_string(), res.clone());
         res
     }
+    for node in nodes {
+        compute_path(node, next, metrics, &mut cache);
+    }
+    cache
+}
+
+fn find_critical_path(
+    config: &Config,
+    metrics: &HashMap<String, Duration>,
+    task_names: &[String],
+) -> (Vec<String>, Duration) {
+    let deps = |n: &str| config.tasks.get(n).map(|t| t.prerequisites()).unwrap_or_default();
+    let chains = longest_chains(task_names, &deps, metrics);
     let mut longest_path = Vec::new();
     let mut max_total = Duration::ZERO;
     for task in task_names {
-        let (path, dur) = compute_path(task, config, metrics, &mut cache);
-        if dur > max_total {
-            max_total = dur;
-            longest_path = path;
+        let (path, dur) = &chains[task];
+        if *dur > max_total {
+            max_total = *dur;
+            // Chains walk from a task down to its deepest dependency; show them in run order
+            longest_path = path.iter().rev().cloned().collect();
         }
     }
     (longest_path, max_total)
@@ -515,11 +1211,13 @@ fn print_summary(s: &RunSummary, config: &Config, task_names: &[String]) {
     };
     println!("\n{}", "Summary:".bold());
     println!(
-        "  {} succeeded, {} cached, {} warned, {} failed",
+        "  {} succeeded, {} cached, {} skipped, {} warned, {} failed, {} cancelled",
         s.succeeded.to_string().green(),
         s.cached.to_string().cyan(),
+        s.skipped.to_string().dimmed(),
         s.warned.to_string().yellow(),
-        s.failed.to_string().red()
+        s.failed.to_string().red(),
+        s.cancelled.to_string().magenta()
     );
     println!(
         "  Total time: {:.2?} ({} saved via parallelism)",
@@ -539,25 +1237,127 @@ fn print_summary(s: &RunSummary, config: &Config, task_names: &[String]) {
             }
         }
     }
+    if !s.nested.is_empty() {
+        println!("\n{}", "Nested runs:".bold().dimmed());
+        for (parent, outcomes) in &s.nested {
+            for (task, status) in outcomes {
+                println!("  {} ↳ {} ({})", parent.cyan(), task, status);
+            }
+        }
+    }
+}
+
+/// How a group's members ended, e.g. "2 ok, 1 cached".
+fn group_breakdown(outcomes: &[(String, &'static str)], members: &[String]) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for member in members {
+        let Some((_, status)) = outcomes.iter().find(|(n, _)| n == member) else { continue };
+        match counts.iter_mut().find(|(s, _)| s == status) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((status, 1)),
+        }
+    }
+    counts.iter().map(|(s, c)| format!("{} {}", c, s)).collect::<Vec<_>>().join(", ")
+}
+
+/// Whether a requested task name is meant as a glob like `lint-*`.
+fn is_glob(name: &str) -> bool {
+    name.contains(['*', '?', '['])
+}
+
+/// Replaces glob task names and adds `--match` regexes with the public tasks
+/// they match. A pattern matching nothing is an error unless `allow_empty`.
+fn select_by_pattern(
+    config: &Config,
+    requested: Vec<String>,
+    regexes: &[String],
+    allow_empty: bool,
+) -> Result<Vec<String>, ZettenError> {
+    let mut selected = Vec::new();
+    let matching = |pattern: &str, is_match: &dyn Fn(&str) -> bool| -> Result<Vec<String>, ZettenError> {
+        let hits: Vec<String> = config.visible_tasks().into_iter().filter(|n| is_match(n)).cloned().collect();
+        if hits.is_empty() && !allow_empty {
+            return Err(ZettenError::NoTasksMatch(pattern.to_string()));
+        }
+        Ok(hits)
+    };
+
+    for name in requested {
+        // Real task names win, so an instance name like `test[db=pg]` is reported
+        // as private (use `--matrix db=pg`) instead of being read as a pattern
+        if !is_glob(&name) || config.resolve_name(&name).is_some() {
+            selected.push(name);
+            continue;
+        }
+        let glob = globset::Glob::new(&name)
+            .map_err(|e| anyhow::anyhow!("USER_ERROR: Invalid task pattern '{}': {}", name, e))?
+            .compile_matcher();
+        selected.extend(matching(&name, &|n| glob.is_match(n))?);
+    }
+    for pattern in regexes {
+        let re = regex::Regex::new(pattern)
+            .map_err(|e| anyhow::anyhow!("USER_ERROR: Invalid --match regex '{}': {}", pattern, e))?;
+        selected.extend(matching(pattern, &|n| re.is_match(n))?);
+    }
+    Ok(selected)
+}
+
+fn no_matrix_combination(task: &str) -> ZettenError {
+    ZettenError::Anyhow(anyhow::anyhow!("USER_ERROR: No matrix combination of '{}' matches --matrix", task))
 }
 
-fn collect_tasks(config: &Config, roots: &[String]) -> Result<Vec<String>, ZettenError> { // Return explicit ZettenError result
+/// Plans `roots` and everything they need (only their setups and teardowns
+/// without `with_deps`). `allow_private` lets a re-run name helpers and
+/// instances that failed last time.
+fn collect_tasks(
+    config: &Config,
+    roots: &[String],
+    allow_private: bool,
+    with_deps: bool,
+) -> Result<Vec<String>, ZettenError> { // Return explicit ZettenError result
     let mut expanded = HashSet::new();
-    let mut stack = roots.to_vec();
+    let mut stack = Vec::new();
+
+    // Roots may be aliases, but never private tasks
+    for root in roots {
+        match config.resolve_name(root) {
+            Some(name) if config.tasks[name].private && !allow_private => {
+                return Err(ZettenError::TaskPrivate(name.to_string()));
+            }
+            // A matrix task stands for all of its selected combinations
+            Some(name) if !config.tasks[name].matrix.is_empty() => {
+                let instances = &config.tasks[name].matrix_instances;
+                if instances.is_empty() {
+                    return Err(no_matrix_combination(name));
+                }
+                stack.extend(instances.iter().cloned());
+            }
+            Some(name) => stack.push(name.to_string()),
+            None => {
+                // Fuzzy search
+                if let Some(closest) = find_closest(root, config.public_names()) {
+                     return Err(ZettenError::TaskNotFoundFuzzy(root.clone(), closest.to_string()));
+                }
+                return Err(ZettenError::TaskNotFound(root.clone()));
+            }
+        }
+    }
     
-    // First pass: Expand dependencies
+    // First pass: Expand dependencies, setups and teardowns
     while let Some(t) = stack.pop() {
         if expanded.insert(t.clone()) {
             if let Some(c) = config.tasks.get(&t) {
-                for d in &c.depends_on {
-                    stack.push(d.clone());
+                // Only a matrix whose combinations --matrix all filtered out is left unexpanded
+                if !c.matrix.is_empty() {
+                    return Err(no_matrix_combination(&t));
                 }
-            } else {
-                // Fuzzy search
-                let keys: Vec<&str> = config.tasks.keys().map(|s| s.as_str()).collect();
-                if let Some(closest) = find_closest(&t, keys) {
-                     return Err(ZettenError::TaskNotFoundFuzzy(t, closest.to_string()));
+                if with_deps {
+                    stack.extend(c.prerequisites());
+                } else {
+                    stack.extend(c.setup.clone());
                 }
+                stack.extend(c.teardown.clone());
+            } else {
                 return Err(ZettenError::TaskNotFound(t));
             }
         }
@@ -567,9 +1367,27 @@ fn collect_tasks(config: &Config, roots: &[String]) -> Result<Vec<String>, Zette
     let mut visited = HashSet::new();
     let mut visiting = HashSet::new();
 
+    // A teardown comes after every planned task that uses it; `after` and
+    // `optional_depends_on` only order tasks that are planned anyway
+    let mut expanded: Vec<String> = expanded.into_iter().collect();
+    expanded.sort();
+    let mut before: HashMap<String, Vec<String>> = expanded
+        .iter()
+        .map(|n| {
+            let task = &config.tasks[n];
+            let deps = task.prerequisites().into_iter().chain(task.soft_dependencies());
+            (n.clone(), deps.filter(|d| expanded.binary_search(d).is_ok()).collect())
+        })
+        .collect();
+    for n in &expanded {
+        if let Some(td) = &config.tasks[n].teardown {
+            before.get_mut(td).unwrap().push(n.clone());
+        }
+    }
+
     fn visit(
         n: &str,
-        cfg: &Config,
+        before: &HashMap<String, Vec<String>>,
         s: &mut Vec<String>,
         v: &mut HashSet<String>,
         vg: &mut HashSet<String>,
@@ -579,10 +1397,8 @@ fn collect_tasks(config: &Config, roots: &[String]) -> Result<Vec<String>, Zette
         }
         if !v.contains(n) {
             vg.insert(n.to_string());
-            if let Some(t) = cfg.tasks.get(n) {
-                for d in &t.depends_on {
-                    visit(d, cfg, s, v, vg)?;
-                }
+            for d in &before[n] {
+                visit(d, before, s, v, vg)?;
             }
             vg.remove(n);
             v.insert(n.to_string());
@@ -591,12 +1407,72 @@ fn collect_tasks(config: &Config, roots: &[String]) -> Result<Vec<String>, Zette
         Ok(())
     }
 
-    for n in expanded {
-        visit(&n, config, &mut sorted, &mut visited, &mut visiting)?;
+    // Visit in name order so the plan is identical from run to run
+    for n in &expanded {
+        visit(n, &before, &mut sorted, &mut visited, &mut visiting)?;
     }
     Ok(sorted)
 }
 
+/// Cuts the plan down to the stretch between `--from` and `--until`
+/// (inclusive). A matrix task counts from its first to its last instance.
+fn slice_plan(
+    config: &Config,
+    plan: Vec<String>,
+    from: Option<&str>,
+    until: Option<&str>,
+) -> Result<Vec<String>, ZettenError> {
+    let positions = |name: &str| -> Result<Vec<usize>, ZettenError> {
+        let task = config.resolve_name(name).ok_or_else(|| ZettenError::TaskNotFound(name.to_string()))?;
+        let members = &config.tasks[task].matrix_instances;
+        let found: Vec<usize> = plan
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| *n == task || members.contains(n))
+            .map(|(i, _)| i)
+            .collect();
+        if found.is_empty() {
+            return Err(ZettenError::Anyhow(anyhow::anyhow!(
+                "USER_ERROR: Task '{}' is not part of this run",
+                name
+            )));
+        }
+        Ok(found)
+    };
+    let start = from.map(positions).transpose()?.map_or(0, |p| p[0]);
+    let end = until.map(positions).transpose()?.map_or(plan.len(), |p| p[p.len() - 1] + 1);
+    if start >= end {
+        return Err(ZettenError::Anyhow(anyhow::anyhow!(
+            "USER_ERROR: '{}' comes after '{}' in this run, so nothing is left between them",
+            from.unwrap_or_default(),
+            until.unwrap_or_default()
+        )));
+    }
+
+    // Setups and teardowns of kept tasks stay, even when ordered outside the range
+    let kept = plan[start..end].iter().map(String::as_str).collect();
+    Ok(keep_with_lifecycle(config, &plan, kept))
+}
+
+/// The tasks of `plan` in `kept`, plus the setups and teardowns they use,
+/// in plan order.
+fn keep_with_lifecycle<'a>(config: &Config, plan: &'a [String], mut kept: HashSet<&'a str>) -> Vec<String> {
+    let mut grew = true;
+    while grew {
+        grew = false;
+        for n in plan {
+            let task = &config.tasks[n];
+            for hook in [task.setup.as_deref(), task.teardown.as_deref()].into_iter().flatten() {
+                let planned = plan.iter().find(|p| *p == hook);
+                if kept.contains(n.as_str()) && planned.is_some_and(|p| kept.insert(p.as_str())) {
+                    grew = true;
+                }
+            }
+        }
+    }
+    plan.iter().filter(|n| kept.contains(n.as_str())).cloned().collect()
+}
+
 fn find_closest<'a>(i: &str, opts: Vec<&'a str>) -> Option<&'a str> {
     opts.into_iter()
         .map(|o| (o, strsim::levenshtein(i, o)))
@@ -605,33 +1481,3 @@ fn find_closest<'a>(i: &str, opts: Vec<&'a str>) -> Option<&'a str> {
         .map(|(o, _)| o)
 }
 
-fn matches_tag_expression(expr: &str, tags: &[String]) -> bool {
-    // supported: "ci", "ci+slow" (AND), "ci,!slow" (OR/NOT mixed is tricky without parser, let's do simple)
-    // Simple logic: 
-    // ',' = OR
-    // '+' = AND
-    // '!' = NOT
-    // split by comma (OR groups)
-    for group in expr.split(',') {
-        // inside group, all must match (AND)
-        let mut group_match = true;
-        for part in group.split('+') {
-            let part = part.trim();
-            if let Some(negated) = part.strip_prefix('!') {
-                if tags.contains(&negated.to_string()) {
-                    group_match = false;
-                    break;
-                }
-            } else {
-                if !tags.contains(&part.to_string()) {
-                     group_match = false;
-                     break;
-                }
-            }
-        }
-        if group_match {
-            return true;
-        }
-    }
-    false
-}
@@ -24,6 +24,11 @@ impl Progress {
     }
 
 
+    pub fn start_task(&self) {
+        let active = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.pb.set_message(format!("⚙️  {} tasks in flight...", active));
+    }
+
     pub fn finish_task(&self) {
         let active = self.running.fetch_sub(1, Ordering::SeqCst) - 1;
         if active > 0 {
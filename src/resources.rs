@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+/// Tracks how much of each named resource running tasks hold, plus the
+/// worker-slot budget from `--workers`. Resources without a declared
+/// capacity are exclusive (capacity 1).
+pub struct ResourcePool {
+    capacity: HashMap<String, u32>,
+    in_use: HashMap<String, u32>,
+    slots: u32,
+    slots_in_use: u32,
+}
+
+impl ResourcePool {
+    pub fn new(capacity: &HashMap<String, u32>, slots: u32) -> Self {
+        Self {
+            capacity: capacity.clone(),
+            in_use: HashMap::new(),
+            slots,
+            slots_in_use: 0,
+        }
+    }
+
+    pub fn capacity_of(&self, name: &str) -> u32 {
+        self.capacity.get(name).copied().unwrap_or(1)
+    }
+
+    /// Claims `weight` worker slots and every named resource, or nothing.
+    pub fn try_acquire(&mut self, claims: &HashMap<String, u32>, weight: u32) -> bool {
+        let fits = self.slots_in_use + weight <= self.slots
+            && claims.iter().all(|(name, amount)| {
+                self.in_use.get(name).copied().unwrap_or(0) + amount <= self.capacity_of(name)
+            });
+        if fits {
+            self.slots_in_use += weight;
+            for (name, amount) in claims {
+                *self.in_use.entry(name.clone()).or_default() += amount;
+            }
+        }
+        fits
+    }
+
+    pub fn release(&mut self, claims: &HashMap<String, u32>, weight: u32) {
+        self.slots_in_use = self.slots_in_use.saturating_sub(weight);
+        for (name, amount) in claims {
+            if let Some(used) = self.in_use.get_mut(name) {
+                *used = used.saturating_sub(*amount);
+            }
+        }
+    }
+}
@@ -1,9 +1,11 @@
-use std::process::{Command, Stdio};
+use std::collections::HashMap;
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::time::{Instant, Duration};
 use std::io::Read;
 use std::path::Path;
 use anyhow::{Result, anyhow};
-use crate::PROCESS_REGISTRY;
+use std::sync::atomic::Ordering;
+use crate::{CANCEL_REQUESTED, INTERRUPTED, PROCESS_REGISTRY};
 
 #[derive(Default, Clone)]
 pub struct ExecutionResult {
@@ -19,46 +21,25 @@ pub fn execute_task_command(
     allow_exit_codes: &[i32], 
     is_parallel: bool,
     interactive: bool,
+    env: &HashMap<String, String>,
 ) -> Result<ExecutionResult> {
     let start = Instant::now();
 
-    // --- AUTO-VENV LOGIC ---
-    // Prepend virtual env bins to PATH so the shell finds them first
-    let mut path_env = std::env::var_os("PATH").unwrap_or_default();
-    let venv_paths = if cfg!(target_os = "windows") {
-        vec![".venv\\Scripts", "venv\\Scripts"]
-    } else {
-        vec![".venv/bin", "venv/bin"]
-    };
-
-    for venv_path in venv_paths {
-        if Path::new(venv_path).exists() {
-            let mut new_path = std::ffi::OsString::from(venv_path);
-            new_path.push(if cfg!(target_os = "windows") { ";" } else { ":" });
-            new_path.push(&path_env);
-            path_env = new_path;
-            break; // Use the first one found
-        }
-    }
-
-    // --- COMMAND SETUP ---
-    let mut command = if cfg!(target_os = "windows") {
-        let mut c = Command::new("cmd");
-        c.args(["/C", cmd_str]);
-        c
-    } else {
-        let mut c = Command::new("sh");
-        c.args(["-c", cmd_str]);
-        c
-    };
-
-    command.env("PATH", path_env);
+    let mut command = shell_command(cmd_str);
+    command.envs(env);
 
     // PILLAR 3: Output Handling
     // In parallel mode, we pipe so we can buffer logs. 
     // In serial mode, we inherit for real-time interaction.
     if is_parallel && !interactive {
-        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        command.stdout(Stdio::piped()).stderr(Stdio::piped()).stdin(Stdio::null());
+        // Own process group, so cancelling also reaches whatever the shell spawned.
+        // Nested runs stay in the parent's group so its cancellation reaches them too.
+        #[cfg(unix)]
+        if !crate::session::is_nested() {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
     } else {
         command.stdout(Stdio::inherit()).stderr(Stdio::inherit());
         if interactive {    
@@ -99,51 +80,22 @@ pub fn execute_task_command(
         (None, None)
     };
 
-    // Register the child handle immediately
+    // Register the PID so Ctrl+C can kill it, then block until it exits.
+    // No lock is held while waiting, so other workers are never stalled.
     {
         let mut registry = PROCESS_REGISTRY.lock().unwrap();
-        registry.push(child);
-    }
-
-    // --- MONITORING LOOP ---
-    let status = loop {
-        std::thread::sleep(Duration::from_millis(50));
-        let mut registry = PROCESS_REGISTRY.lock().unwrap();
-        
-        if let Some(pos) = registry.iter_mut().position(|c| c.id() == child_id) {
-            match registry[pos].try_wait()? {
-                Some(status) => {
-                    // Task finished - remove from registry
-                    let _ = registry.remove(pos);
-                    break Some(status);
-                }
-                None => {
-                    // Still running, drop lock to allow other threads to access registry
-                    drop(registry);
-                }
-            }
+        if CANCEL_REQUESTED.load(Ordering::SeqCst) {
+            // Lost the race with terminate_all(): don't let this one escape
+            kill_process(child_id);
         } else {
-            // If the ID is gone, the Ctrl+C handler killed the process and cleared the registry
-            break None;
+            registry.insert(child_id);
         }
-    };
+    }
+    let status = reap(&mut child)?;
 
-    let (exit_code, stdout_final, stderr_final) = match status {
-        Some(s) => {
-            let stdout = if let Some(t) = stdout_thread {
-                t.join().unwrap_or_default()
-            } else {
-                Vec::new()
-            };
-            let stderr = if let Some(t) = stderr_thread {
-                t.join().unwrap_or_default()
-            } else {
-                Vec::new()
-            };
-            (s.code().unwrap_or(0), stdout, stderr)
-        },
-        None => (130, Vec::new(), Vec::new()),
-    };
+    let stdout_final = stdout_thread.map(|t| t.join().unwrap_or_default()).unwrap_or_default();
+    let stderr_final = stderr_thread.map(|t| t.join().unwrap_or_default()).unwrap_or_default();
+    let exit_code = exit_code_of(&status);
 
     let is_success = exit_code == 0 || allow_exit_codes.contains(&exit_code);
 
@@ -154,4 +106,180 @@ pub fn execute_task_command(
         stdout: stdout_final,
         stderr: stderr_final,
     })
-}
\ No newline at end of file
+}
+
+/// Rough budget for appended file arguments, well below the OS limits
+/// (cmd.exe allows 8191 characters, POSIX systems usually far more).
+const MAX_FILE_ARGS_LEN: usize = if cfg!(windows) { 6_000 } else { 100_000 };
+
+/// Runs `cmd_str` with `files` appended as arguments, split over as many
+/// invocations as the argument-length limit needs. Stops at the first
+/// failing batch.
+pub fn execute_with_files(
+    cmd_str: &str,
+    files: &[String],
+    allow_exit_codes: &[i32],
+    is_parallel: bool,
+    interactive: bool,
+    env: &HashMap<String, String>,
+) -> Result<ExecutionResult> {
+    let mut batches: Vec<Vec<String>> = vec![Vec::new()];
+    let mut len = 0;
+    for file in files {
+        let quoted = crate::git::quote_all(std::slice::from_ref(file));
+        if len + quoted.len() + 1 > MAX_FILE_ARGS_LEN && !batches.last().unwrap().is_empty() {
+            batches.push(Vec::new());
+            len = 0;
+        }
+        len += quoted.len() + 1;
+        batches.last_mut().unwrap().push(quoted);
+    }
+
+    let mut total = ExecutionResult { is_success: true, ..Default::default() };
+    for batch in batches {
+        let cmd = format!("{} {}", cmd_str, batch.join(" "));
+        let exec = execute_task_command(&cmd, allow_exit_codes, is_parallel, interactive, env)?;
+        total.duration += exec.duration;
+        total.stdout.extend(exec.stdout);
+        total.stderr.extend(exec.stderr);
+        total.exit_code = exec.exit_code;
+        if !exec.is_success {
+            total.is_success = false;
+            break;
+        }
+    }
+    Ok(total)
+}
+
+/// Builds the platform shell invocation for `cmd_str` with the project's
+/// virtual env (if any) placed first on PATH.
+pub fn shell_command(cmd_str: &str) -> Command {
+    // --- AUTO-VENV LOGIC ---
+    // Prepend virtual env bins to PATH so the shell finds them first
+    let mut path_env = std::env::var_os("PATH").unwrap_or_default();
+    let venv_paths = if cfg!(target_os = "windows") {
+        vec![".venv\\Scripts", "venv\\Scripts"]
+    } else {
+        vec![".venv/bin", "venv/bin"]
+    };
+
+    for venv_path in venv_paths {
+        if Path::new(venv_path).exists() {
+            let mut new_path = std::ffi::OsString::from(venv_path);
+            new_path.push(if cfg!(target_os = "windows") { ";" } else { ":" });
+            new_path.push(&path_env);
+            path_env = new_path;
+            break; // Use the first one found
+        }
+    }
+
+    let mut command = if cfg!(target_os = "windows") {
+        let mut c = Command::new("cmd");
+        c.args(["/C", cmd_str]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", cmd_str]);
+        c
+    };
+
+    command.env("PATH", path_env);
+    command
+}
+
+/// Maps an exit status to a shell-style code; signal deaths become 128+N
+/// so a killed task is never mistaken for a success.
+fn exit_code_of(status: &ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(sig) = status.signal() {
+            return 128 + sig;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Kills every running task process and stops new ones from spawning
+/// until `reset_cancellation` is called.
+pub fn terminate_all() {
+    if let Ok(mut registry) = PROCESS_REGISTRY.lock() {
+        CANCEL_REQUESTED.store(true, Ordering::SeqCst);
+        for pid in registry.drain() {
+            kill_process(pid);
+        }
+    }
+}
+
+pub fn cancellation_requested() -> bool {
+    CANCEL_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Lets processes spawn again after fail-fast, but never after Ctrl+C.
+pub fn reset_cancellation() {
+    if !interrupted() {
+        CANCEL_REQUESTED.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Ctrl+C: kill everything and refuse new processes for good.
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    terminate_all();
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Forcefully stops a spawned task by PID.
+pub fn kill_process(pid: u32) {
+    #[cfg(unix)]
+    unsafe {
+        // Captured tasks lead their own group; fall back to the bare PID otherwise
+        if libc::kill(-(pid as libc::pid_t), libc::SIGKILL) != 0 {
+            libc::kill(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+    #[cfg(windows)]
+    {
+        let _ = Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+/// Waits for `child` to exit, then drops it from the registry before
+/// reaping it. Until it is reaped its PID cannot be reused, so
+/// `terminate_all` never signals a stranger.
+pub fn reap(child: &mut Child) -> std::io::Result<ExitStatus> {
+    #[cfg(unix)]
+    while !wait_for_exit(child.id(), 0)? {}
+    PROCESS_REGISTRY.lock().unwrap().remove(&child.id());
+    child.wait()
+}
+
+/// Whether `child` has exited, without reaping it.
+pub fn has_exited(child: &mut Child) -> std::io::Result<bool> {
+    #[cfg(unix)]
+    return wait_for_exit(child.id(), libc::WNOHANG);
+    #[cfg(not(unix))]
+    return Ok(child.try_wait()?.is_some());
+}
+
+/// `waitid` with `WNOWAIT`: true once `pid` has exited, leaving it a zombie
+/// for `Child::wait` to collect. An interrupted wait reports false.
+#[cfg(unix)]
+fn wait_for_exit(pid: u32, flags: libc::c_int) -> std::io::Result<bool> {
+    unsafe {
+        let mut info: libc::siginfo_t = std::mem::zeroed();
+        if libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, libc::WEXITED | libc::WNOWAIT | flags) != 0 {
+            let err = std::io::Error::last_os_error();
+            return if err.kind() == std::io::ErrorKind::Interrupted { Ok(false) } else { Err(err) };
+        }
+        // With WNOHANG a child that is still running leaves si_pid at 0
+        Ok(info.si_pid() != 0)
+    }
+}
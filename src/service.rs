@@ -0,0 +1,185 @@
+//! Background service tasks (`service = true`).
+//!
+//! A service is started like any task but only waited on until its
+//! `ready_when` checks pass. It then keeps running while its dependents do
+//! and is stopped once the last of them has finished.
+
+use crate::config::ReadyCheck;
+use crate::runner::{self, ExecutionResult};
+use crate::PROCESS_REGISTRY;
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    /// Services that are up, by task name
+    static ref RUNNING: Mutex<HashMap<String, Child>> = Mutex::new(HashMap::new());
+}
+
+/// Starts `name` in the background and blocks until it is ready, exits or
+/// times out. Only a ready service is left running.
+pub fn start(
+    name: &str,
+    cmd: &str,
+    env: &HashMap<String, String>,
+    check: Option<&ReadyCheck>,
+) -> Result<ExecutionResult> {
+    let start = Instant::now();
+    let check = check.cloned().unwrap_or_default();
+    let pattern = check.log_regex.as_deref().map(Regex::new).transpose()?;
+
+    let mut command = runner::shell_command(cmd);
+    command.envs(env).stdout(Stdio::piped()).stderr(Stdio::piped()).stdin(Stdio::null());
+    #[cfg(unix)]
+    if !crate::session::is_nested() {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command.spawn().map_err(|e| anyhow!("Failed to spawn service '{}': {}", name, e))?;
+    let pid = child.id();
+    {
+        let mut registry = PROCESS_REGISTRY.lock().unwrap();
+        if runner::cancellation_requested() {
+            runner::kill_process(pid);
+        } else {
+            registry.insert(pid);
+        }
+    }
+
+    // Keep draining both pipes for the service's whole life so it never
+    // blocks on a full pipe; output is only kept until it is ready
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let matched = Arc::new(AtomicBool::new(false));
+    let ready = Arc::new(AtomicBool::new(false));
+    let readers: Vec<Box<dyn Read + Send>> = vec![
+        Box::new(child.stdout.take().unwrap()),
+        Box::new(child.stderr.take().unwrap()),
+    ];
+    for pipe in readers {
+        let (log, matched, ready, pattern) = (log.clone(), matched.clone(), ready.clone(), pattern.clone());
+        thread::spawn(move || {
+            for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                if ready.load(Ordering::SeqCst) {
+                    continue;
+                }
+                if pattern.as_ref().is_some_and(|p| p.is_match(&line)) {
+                    matched.store(true, Ordering::SeqCst);
+                }
+                let mut log = log.lock().unwrap();
+                log.extend_from_slice(line.as_bytes());
+                log.push(b'\n');
+            }
+        });
+    }
+
+    let timeout = Duration::from_secs(check.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let failure = |exit_code: i32, reason: String| {
+        let mut stderr = log.lock().unwrap().clone();
+        stderr.extend_from_slice(reason.as_bytes());
+        ExecutionResult {
+            exit_code,
+            is_success: false,
+            duration: start.elapsed(),
+            stdout: Vec::new(),
+            stderr,
+        }
+    };
+    loop {
+        if runner::has_exited(&mut child)? {
+            let status = runner::reap(&mut child)?;
+            let code = status.code().filter(|c| *c != 0).unwrap_or(1);
+            return Ok(failure(code, format!("Service '{}' exited before it was ready", name)));
+        }
+        let up = (pattern.is_none() || matched.load(Ordering::SeqCst))
+            && check.port.is_none_or(port_open)
+            && check.http.as_deref().is_none_or(http_ok);
+        if up {
+            break;
+        }
+        if runner::cancellation_requested() || start.elapsed() > timeout {
+            runner::kill_process(pid);
+            let _ = runner::reap(&mut child);
+            return Ok(failure(1, format!("Service '{}' was not ready after {:?}", name, timeout)));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    ready.store(true, Ordering::SeqCst);
+    RUNNING.lock().unwrap().insert(name.to_string(), child);
+    Ok(ExecutionResult {
+        exit_code: 0,
+        is_success: true,
+        duration: start.elapsed(),
+        ..Default::default()
+    })
+}
+
+/// Names of the services currently up.
+pub fn running() -> Vec<String> {
+    RUNNING.lock().unwrap().keys().cloned().collect()
+}
+
+/// Stops a service started by `start`, together with its children.
+pub fn stop(name: &str) {
+    let Some(mut child) = RUNNING.lock().unwrap().remove(name) else { return };
+    runner::kill_process(child.id());
+    let _ = runner::reap(&mut child);
+}
+
+pub fn stop_all() {
+    for name in running() {
+        stop(&name);
+    }
+}
+
+/// Stops every running service when dropped.
+pub struct StopAllOnDrop;
+
+impl Drop for StopAllOnDrop {
+    fn drop(&mut self) {
+        stop_all();
+    }
+}
+
+fn port_open(port: u16) -> bool {
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    TcpStream::connect_timeout(&addr, Duration::from_millis(200)).is_ok()
+}
+
+/// Minimal HTTP/1.0 GET; any 2xx or 3xx status counts as healthy.
+fn http_ok(url: &str) -> bool {
+    let Some(rest) = url.strip_prefix("http://") else { return false };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let host = authority.rsplit_once(':').map_or(authority, |(h, _)| h);
+    let target = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+    let Some(addr) = target.to_socket_addrs().ok().and_then(|mut a| a.next()) else { return false };
+    let Ok(mut stream) = TcpStream::connect_timeout(&addr, Duration::from_millis(500)) else { return false };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+    let request = format!("GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n", path, host);
+    if stream.write_all(request.as_bytes()).is_err() {
+        return false;
+    }
+    let mut status_line = String::new();
+    if BufReader::new(stream).read_line(&mut status_line).is_err() {
+        return false;
+    }
+    status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .is_some_and(|code| (200..400).contains(&code))
+}
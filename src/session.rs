@@ -0,0 +1,252 @@
+use crate::cache::file_key;
+use sha2::{Digest, Sha256};
+use crate::errors::ZettenError;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const SESSION_VAR: &str = "ZTN_SESSION";
+const STACK_VAR: &str = "ZTN_TASK_STACK";
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The directory of the session this process started, if it did; kept
+/// here so the Ctrl+C handler, which exits without unwinding, can remove it.
+static OWNED: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Removes the session directory this process owns, if any.
+pub fn cleanup() {
+    if let Some(dir) = OWNED.lock().unwrap().take() {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+/// State shared by one top-level `ztn run` and every `ztn` its tasks start.
+///
+/// The outermost run owns a directory under `.zetten/sessions/` and exports
+/// it to task processes together with the chain of tasks that led there.
+/// Nested runs find it through the environment and use it to skip work the
+/// session already finished, to wait for work another run of the session
+/// is doing, to catch recursion and to report back.
+pub struct Session {
+    dir: PathBuf,
+    stack: Vec<String>,
+    nested: bool,
+}
+
+/// True when this process was started by a task of another `ztn run`.
+pub fn is_nested() -> bool {
+    std::env::var_os(SESSION_VAR).is_some()
+}
+
+impl Session {
+    /// Joins the parent's session if there is one for this project,
+    /// otherwise starts a new one.
+    pub fn attach(root: &Path) -> Result<Self, ZettenError> {
+        if let Ok(dir) = std::env::var(SESSION_VAR) {
+            let dir = PathBuf::from(dir);
+            if dir.starts_with(root) && dir.exists() {
+                let stack = std::env::var(STACK_VAR)
+                    .unwrap_or_default()
+                    .split('>')
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect();
+                return Ok(Self { dir, stack, nested: true });
+            }
+        }
+
+        let dir = root.join(".zetten/sessions").join(std::process::id().to_string());
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("done"))?;
+        fs::create_dir_all(dir.join("running"))?;
+        fs::create_dir_all(dir.join("waiting"))?;
+        *OWNED.lock().unwrap() = Some(dir.clone());
+        Ok(Self { dir, stack: Vec::new(), nested: false })
+    }
+
+    pub fn is_nested(&self) -> bool {
+        self.nested
+    }
+
+    /// Fails if the plan would start a task that is already running further
+    /// up the process chain.
+    pub fn check_recursion(&self, plan: &[String]) -> Result<(), ZettenError> {
+        if let Some(task) = plan.iter().find(|t| self.stack.contains(t)) {
+            let mut chain = self.stack.clone();
+            chain.push(task.clone());
+            return Err(ZettenError::RecursiveInvocation(chain.join(" ➔ ")));
+        }
+        Ok(())
+    }
+
+    /// Variables a task's process needs to join this session.
+    pub fn child_env(&self, task: &str) -> HashMap<String, String> {
+        let mut stack = self.stack.clone();
+        stack.push(task.to_string());
+        HashMap::from([
+            (SESSION_VAR.to_string(), self.dir.to_string_lossy().to_string()),
+            (STACK_VAR.to_string(), stack.join(">")),
+        ])
+    }
+
+    /// Names one piece of work for the done-markers: the same task with other
+    /// arguments, variables or profile env is different work.
+    pub fn work_key(task: &str, cmd: &str, env: &HashMap<String, String>) -> String {
+        let mut env: Vec<_> = env.iter().collect();
+        env.sort();
+        let digest = format!("{:x}", Sha256::digest(format!("{}\n{:?}", cmd, env)));
+        format!("{}-{}", file_key(task), &digest[..12])
+    }
+
+    pub fn mark_done(&self, work: &str) {
+        let _ = fs::write(self.dir.join("done").join(work), "");
+    }
+
+    /// Undoes `mark_done`, e.g. once a service has been stopped.
+    pub fn forget(&self, work: &str) {
+        let _ = fs::remove_file(self.dir.join("done").join(work));
+    }
+
+    /// Whether any run in this session already completed this `work_key`.
+    pub fn is_done(&self, work: &str) -> bool {
+        self.dir.join("done").join(work).exists()
+    }
+
+    /// Takes `work` (running `task`) for this process. When another run of
+    /// the session holds it, waits for that run to finish it instead of
+    /// doing it a second time; if it gives up on the work unfinished, takes
+    /// it over. Waiting on a run that in turn waits on us is a recursion.
+    pub fn claim(&self, task: &str, work: &str, cancelled: impl Fn() -> bool) -> Result<Claim, ZettenError> {
+        let path = self.dir.join("running").join(work);
+        let us = self.stack.join(">");
+        let mut chain = self.stack.clone();
+        chain.push(task.to_string());
+        let waiting = self.dir.join("waiting").join(format!("{}-{}", std::process::id(), work));
+        let result = loop {
+            if self.is_done(work) {
+                break Ok(Claim::Done);
+            }
+            // The claim records where the work runs, as a task chain
+            if let Ok(mut file) = fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                let _ = file.write_all(chain.join(">").as_bytes());
+                break Ok(Claim::Ours(WorkClaim { path }));
+            }
+            let holder = fs::read_to_string(&path).unwrap_or_default();
+            let _ = fs::write(&waiting, format!("{}\n{}", us, holder));
+            if !holder.is_empty() && self.waits_on(&holder, &us) {
+                break Err(ZettenError::RecursiveInvocation(chain.join(" ➔ ")));
+            }
+            if cancelled() {
+                break Ok(Claim::Cancelled);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+        let _ = fs::remove_file(&waiting);
+        result
+    }
+
+    /// Whether the task chain `holder`, or anything it started, is waiting
+    /// (directly or through others) on work held at `us` or above it.
+    fn waits_on(&self, holder: &str, us: &str) -> bool {
+        let within = |chain: &str, top: &str| chain == top || chain.starts_with(&format!("{}>", top));
+        let waits: Vec<(String, String)> = fs::read_dir(self.dir.join("waiting"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|e| {
+                let contents = fs::read_to_string(e.path()).ok()?;
+                let (waiter, held_by) = contents.split_once('\n')?;
+                Some((waiter.to_string(), held_by.to_string()))
+            })
+            .collect();
+        let mut frontier = vec![holder.to_string()];
+        let mut seen = Vec::new();
+        while let Some(h) = frontier.pop() {
+            if within(us, &h) {
+                return true;
+            }
+            if seen.contains(&h) {
+                continue;
+            }
+            for (waiter, held_by) in &waits {
+                if within(waiter, &h) {
+                    frontier.push(held_by.clone());
+                }
+            }
+            seen.push(h);
+        }
+        false
+    }
+
+    /// Drops the claims of nested runs started by `task`, which has ended;
+    /// a nested run that was killed never released its own.
+    pub fn release_orphans(&self, task: &str) {
+        if self.nested {
+            return;
+        }
+        let prefix = format!("{}>", task);
+        for entry in fs::read_dir(self.dir.join("running")).into_iter().flatten().flatten() {
+            if fs::read_to_string(entry.path()).is_ok_and(|chain| chain.starts_with(&prefix)) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    /// Called by a nested run: leaves its task outcomes for the parent task.
+    pub fn report(&self, outcomes: &[(String, String)]) {
+        let Some(parent_task) = self.stack.last() else { return };
+        let dir = self.dir.join("nested").join(file_key(parent_task));
+        let lines: Vec<String> = outcomes.iter().map(|(t, status)| format!("{} {}", status, t)).collect();
+        if fs::create_dir_all(&dir).is_ok() {
+            let _ = fs::write(dir.join(std::process::id().to_string()), lines.join("\n"));
+        }
+    }
+
+    /// Outcomes reported by nested runs started from `task`.
+    pub fn nested_outcomes(&self, task: &str) -> Vec<(String, String)> {
+        let Ok(entries) = fs::read_dir(self.dir.join("nested").join(file_key(task))) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|e| fs::read_to_string(e.ok()?.path()).ok())
+            .flat_map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(|l| l.split_once(' ').map(|(s, t)| (t.to_string(), s.to_string())))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+impl Drop for Session {
+    /// The outermost run cleans up after everyone.
+    fn drop(&mut self) {
+        if !self.nested {
+            cleanup();
+        }
+    }
+}
+
+/// What `Session::claim` came back with.
+pub enum Claim {
+    /// This process does the work; dropping the claim releases it.
+    Ours(WorkClaim),
+    /// Some run of the session already finished it.
+    Done,
+    /// The run was cancelled while waiting for someone else's work.
+    Cancelled,
+}
+
+pub struct WorkClaim {
+    path: PathBuf,
+}
+
+impl Drop for WorkClaim {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,162 @@
+//! Boolean tag expressions for `--tag`.
+//!
+//! ```text
+//! expr  := and (("or" | "," | "|" | "||") and)*
+//! and   := unary (("and" | "+" | "&" | "&&") unary)*
+//! unary := ("not" | "!") unary | "(" expr ")" | TAG
+//! ```
+//!
+//! Keywords are case-insensitive and whitespace is optional around symbols,
+//! so the older compact form (`ci+!slow,manual`) still parses.
+
+use crate::errors::ZettenError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+impl TagExpr {
+    pub fn parse(input: &str) -> Result<Self, ZettenError> {
+        let mut parser = Parser { input, tokens: tokenize(input), pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some((Token::RParen, span)) => Err(parser.error(span, "unmatched ')'")),
+            Some((_, span)) => Err(parser.error(span, "expected 'and', 'or' or the end of the expression")),
+        }
+    }
+
+    pub fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            TagExpr::Tag(t) => tags.contains(t),
+            TagExpr::Not(e) => !e.matches(tags),
+            TagExpr::And(a, b) => a.matches(tags) && b.matches(tags),
+            TagExpr::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+/// (offset, length) into the expression
+type Span = (usize, usize);
+
+fn tokenize(input: &str) -> Vec<(Token, Span)> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '!' => Token::Not,
+            '+' => Token::And,
+            ',' => Token::Or,
+            '&' | '|' => {
+                // Accept both `&` and `&&` (and `|` / `||`)
+                let len = if chars.next_if(|&(_, n)| n == c).is_some() { 2 } else { 1 };
+                tokens.push((if c == '&' { Token::And } else { Token::Or }, (start, len)));
+                continue;
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, n)) = chars.next_if(|&(_, n)| !is_delimiter(n)) {
+                    end = i + n.len_utf8();
+                }
+                let word = &input[start..end];
+                let token = match word.to_ascii_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Tag(word.to_string()),
+                };
+                tokens.push((token, (start, end - start)));
+                continue;
+            }
+        };
+        tokens.push((token, (start, c.len_utf8())));
+    }
+    tokens
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()!+,&|".contains(c)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token, Span)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<(Token, Span)> {
+        self.tokens.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek().is_some_and(|(t, _)| &t == token) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn or(&mut self) -> Result<TagExpr, ZettenError> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = TagExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<TagExpr, ZettenError> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = TagExpr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<TagExpr, ZettenError> {
+        let Some((token, span)) = self.peek() else {
+            return Err(match self.tokens.last() {
+                Some((_, last)) => self.error(*last, "expected a tag after this"),
+                None => self.error((0, self.input.len()), "the expression is empty"),
+            });
+        };
+        self.pos += 1;
+        match token {
+            Token::Tag(t) => Ok(TagExpr::Tag(t)),
+            Token::Not => Ok(TagExpr::Not(Box::new(self.unary()?))),
+            Token::LParen => {
+                let expr = self.or()?;
+                if !self.eat(&Token::RParen) {
+                    return Err(self.error(span, "this '(' is never closed"));
+                }
+                Ok(expr)
+            }
+            _ => Err(self.error(span, "expected a tag")),
+        }
+    }
+
+    fn error(&self, span: Span, message: &str) -> ZettenError {
+        ZettenError::InvalidTagExpression {
+            expr: self.input.to_string(),
+            span: span.into(),
+            message: message.to_string(),
+        }
+    }
+}
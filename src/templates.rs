@@ -44,6 +44,7 @@ allow_exit_codes = [0, 5]
 
 [tasks.release]
 description = "Bump version and tag (usage: ztn run release -- v1.0.0)"
+confirm = "This will commit, tag and push to origin main. Continue?"
 cmd = """
 python3 -c "import re; p=re.sub(r'version = \".*\"', 'version = \"$1\"', open('pyproject.toml').read()); open('pyproject.toml', 'w').write(p)" && \
 git add pyproject.toml && \
@@ -57,6 +58,8 @@ inputs = ["pyproject.toml"]
 pub const FASTAPI: &str = r#"[tasks.run]
 cmd = "uvicorn app.main:app --reload"
 inputs = ["app/"]
+service = true
+ready_when = { port = 8000 }
 
 [tasks.test]
 cmd = "pytest"
@@ -1,9 +1,8 @@
 use anyhow::{anyhow, Result};
-use inquire::Select;
+use inquire::{Confirm, Select};
 use crate::config::Config;
 use crate::root;
 use std::env;
-use std::collections::HashMap;
 use crate::templates::TEMPLATES;
 
 pub fn select_template() -> Result<String> {
@@ -17,6 +16,11 @@ pub fn select_template() -> Result<String> {
     Ok(chosen_name)
 }
 
+/// Asks a yes/no question; anything but an explicit yes counts as no.
+pub fn confirm(message: &str) -> bool {
+    Confirm::new(message).with_default(false).prompt().unwrap_or(false)
+}
+
 pub fn show_selector() -> Result<()> {
     // 1. Find project and load config
     let (root, source) = root::find_project_root()
@@ -24,13 +28,12 @@ pub fn show_selector() -> Result<()> {
     env::set_current_dir(&root)?;
     let config = Config::load(&source)?;
 
-    if config.tasks.is_empty() {
+    if config.visible_tasks().is_empty() {
         return Err(anyhow!("USER_ERROR: No tasks defined in zetten.toml"));
     }
 
     // 2. Prepare task names for selection
-    let mut options: Vec<String> = config.tasks.keys().cloned().collect();
-    options.sort();
+    let options: Vec<String> = config.visible_tasks().into_iter().cloned().collect();
 
     // 3. Launch the interactive prompt
     let ans = Select::new("Select a task to run:", options)
@@ -40,8 +43,10 @@ pub fn show_selector() -> Result<()> {
     match ans {
         Ok(choice) => {
             println!("Selected: {}", choice);
-            // Argument #6: HashMap::new() ensures the 3-tier system has a map to check
-            crate::run_tasks(vec![choice], "auto".to_string(), false, vec![], None, HashMap::new())?; // interactive=false
+            crate::run_tasks(crate::RunOptions {
+                tasks: vec![choice],
+                ..Default::default()
+            })?;
             Ok(())
         }
         Err(_) => {
@@ -15,8 +15,19 @@ pub fn validate_execution_env(config: &Config, task_names: &[String]) -> Result<
             .ok_or_else(|| anyhow!("Task {} not found", name))?;
 
         // 1. COMMAND VALIDATION
-        // 1. COMMAND VALIDATION
-        if let Some(cmd) = &task.cmd {
+        if task.is_group() && task.depends_on.is_empty() {
+            errors.push(format!(
+                "{} Task '{}' has neither 'cmd' nor 'script'. Add one, or list tasks in 'depends_on' to make it a group.",
+                "✘".red(), name.bold()
+            ));
+        }
+        if task.cmd.is_some() && task.platform_cmd().is_none() {
+            errors.push(format!(
+                "{} Task '{}' has no command for this platform ({}). Add 'cmd.{}' or 'cmd.default'.",
+                "✘".red(), name.bold(), std::env::consts::OS.yellow(), std::env::consts::OS
+            ));
+        }
+        if let Some(cmd) = task.platform_cmd() {
             let cmd_primary = cmd.split_whitespace().next().unwrap_or("");
             if !cmd_primary.is_empty() && !command_exists(cmd_primary) {
                 let error_msg = format!(
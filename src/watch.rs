@@ -4,7 +4,6 @@ use notify::{Config as NotifyConfig, RecommendedWatcher, RecursiveMode, Watcher,
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
 use colored::*;
 
 pub fn run(initial_config: &Config, task_names: &[String]) -> Result<()> {
@@ -19,8 +18,11 @@ pub fn run(initial_config: &Config, task_names: &[String]) -> Result<()> {
 
     crate::log::info("Precision Watch active. Waiting for changes...");
     
-    // Initial run - Added empty HashMap for Argument #6
-    let _ = crate::run_tasks(task_names.to_vec(), "auto".to_string(), false, vec![], None, HashMap::new()); // interactive=false (default)
+    // Initial run
+    let _ = crate::run_tasks(crate::RunOptions {
+        tasks: task_names.to_vec(),
+        ..Default::default()
+    });
 
     let mut last_event_time = Instant::now();
     let debounce_duration = Duration::from_millis(300);
@@ -62,8 +64,10 @@ pub fn run(initial_config: &Config, task_names: &[String]) -> Result<()> {
                         
                         if !affected.is_empty() {
                             println!("\n{}", "🔄 Changes detected. Re-running affected tasks...".bold().cyan());
-                            // Re-run call - Added empty HashMap for Argument #6
-                            let _ = crate::run_tasks(affected, "auto".to_string(), false, vec![], None, HashMap::new()); // interactive=false (default)
+                            let _ = crate::run_tasks(crate::RunOptions {
+                                tasks: affected,
+                                ..Default::default()
+                            });
                         }
                     }
                     pending_paths.clear();
@@ -98,8 +102,8 @@ fn identify_affected(config: &Config, roots: &[String], paths: &[PathBuf]) -> Ve
         if let Some(task) = config.tasks.get(name) {
             for input in &task.inputs {
                 let input_p = Path::new(input);
-                if paths.iter().any(|p| p.starts_with(input_p) || input_p == p) {
-                    if !affected.contains(name) { affected.push(name.clone()); }
+                if paths.iter().any(|p| p.starts_with(input_p) || input_p == p) && !affected.contains(name) {
+                    affected.push(name.clone());
                 }
             }
         }
@@ -1,4 +1,4 @@
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 use std::fs;
 use tempfile::tempdir;
@@ -6,7 +6,7 @@ use tempfile::tempdir;
 #[test]
 fn test_missing_config() {
     let temp = tempdir().unwrap();
-    let mut cmd = Command::cargo_bin("ztn").unwrap();
+    let mut cmd = cargo_bin_cmd!("ztn");
     
     cmd.current_dir(&temp)
         .arg("run")
@@ -20,7 +20,7 @@ fn test_missing_config() {
 #[test]
 fn test_init_creates_file() {
     let temp = tempdir().unwrap();
-    let mut cmd = Command::cargo_bin("ztn").unwrap();
+    let mut cmd = cargo_bin_cmd!("ztn");
 
     cmd.current_dir(&temp)
         .arg("init")
@@ -40,7 +40,7 @@ fn test_task_not_found_with_suggestion() {
 cmd = "echo built"
 "#).unwrap();
 
-    let mut cmd = Command::cargo_bin("ztn").unwrap();
+    let mut cmd = cargo_bin_cmd!("ztn");
     cmd.current_dir(&temp)
         .arg("run")
         .arg("buid") // Typo
@@ -63,7 +63,7 @@ cmd = "echo b"
 depends_on = ["a"]
 "#).unwrap();
 
-    let mut cmd = Command::cargo_bin("ztn").unwrap();
+    let mut cmd = cargo_bin_cmd!("ztn");
     cmd.current_dir(&temp)
         .arg("run")
         .arg("a")
@@ -71,3 +71,42 @@ depends_on = ["a"]
         .failure()
         .stderr(predicate::str::contains("Circular dependency detected"));
 }
+
+#[test]
+fn test_malformed_tag_expression() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.lint]
+cmd = "echo lint"
+tags = ["ci"]
+"#).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("ztn");
+    cmd.current_dir(&temp)
+        .args(["run", "--tag", "(ci or nightly"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid tag expression"))
+        .stderr(predicate::str::contains("this '(' is never closed"));
+}
+
+#[test]
+fn test_equals_without_env_is_a_config_error() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.build]
+cmd = "echo build"
+
+[tasks.deploy]
+cmd = "echo deploy"
+run_if = { equals = "true" }
+"#).unwrap();
+
+    // Reported up front, even when the task with the condition is not planned
+    let mut cmd = cargo_bin_cmd!("ztn");
+    cmd.current_dir(&temp)
+        .args(["run", "build"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("run_if of task 'deploy' uses 'equals' without 'env'"));
+}
@@ -0,0 +1,1344 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_profile_overrides_vars_env_and_tasks() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[vars]
+TARGET = "local"
+
+[tasks.build]
+cmd = "echo target=${TARGET} mode=$MODE"
+
+[tasks.deploy]
+cmd = "echo deploying"
+
+[profiles.ci.vars]
+TARGET = "wheel"
+
+[profiles.ci.env]
+MODE = "strict"
+
+[profiles.ci.tasks.deploy]
+cmd = "echo deploy skipped in ci"
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "build", "deploy", "--profile", "ci", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("target=wheel mode=strict"))
+        .stdout(predicate::str::contains("deploy skipped in ci"));
+
+    // ZTN_PROFILE selects the same profile, and -k still wins over it
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .env("ZTN_PROFILE", "ci")
+        .args(["run", "build", "-k", "TARGET=sdist"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("target=sdist mode=strict"));
+}
+
+#[test]
+fn test_unknown_profile_is_rejected() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.build]
+cmd = "echo built"
+
+[profiles.ci.vars]
+TARGET = "wheel"
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "build", "--profile", "prod"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown profile 'prod' (available: ci)"));
+}
+
+#[test]
+fn test_platform_specific_command() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.hello]
+cmd.linux = "echo hello from linux"
+cmd.macos = "echo hello from macos"
+cmd.windows = "echo hello from windows"
+
+[tasks.win-only]
+cmd.windows = "dir"
+"#).unwrap();
+
+    let expected = format!("hello from {}", std::env::consts::OS);
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "hello"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(expected));
+
+    if !cfg!(windows) {
+        cargo_bin_cmd!("ztn")
+            .current_dir(&temp)
+            .args(["run", "win-only"])
+            .assert()
+            .failure()
+            .stdout(predicate::str::contains("has no command for this platform"));
+    }
+}
+
+#[test]
+fn test_run_if_and_skip_if() {
+    let temp = tempdir().unwrap();
+    fs::create_dir(temp.path().join("migrations")).unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.docker-build]
+cmd = "echo building image"
+run_if = { env = "CI", equals = "true" }
+
+[tasks.migrate]
+cmd = "echo migrating"
+run_if = { glob = "migrations/*.sql" }
+
+[tasks.lint]
+cmd = "echo linting"
+skip_if = { cmd = "exit 0" }
+
+[tasks.release]
+cmd = "echo releasing"
+depends_on = ["docker-build", "migrate", "lint"]
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .env_remove("CI")
+        .args(["run", "release", "-w", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("docker-build (skipped: run_if not met: CI=true)"))
+        .stdout(predicate::str::contains("migrate (skipped"))
+        .stdout(predicate::str::contains("lint (skipped: skip_if met"))
+        .stdout(predicate::str::contains("1 succeeded, 0 cached, 3 skipped"));
+
+    fs::write(temp.path().join("migrations/0001.sql"), "create table t();").unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "release", "-w", "1", "-k", "CI=true"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("building image"))
+        .stdout(predicate::str::contains("migrating"))
+        .stdout(predicate::str::contains("3 succeeded, 0 cached, 1 skipped"));
+}
+
+#[test]
+fn test_aliases_and_private_tasks() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.test]
+cmd = "echo testing"
+aliases = ["t"]
+depends_on = ["prepare-db"]
+
+[tasks.prepare-db]
+cmd = "echo preparing"
+private = true
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "t", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("preparing"))
+        .stdout(predicate::str::contains("testing"));
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "prepare-db"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Task 'prepare-db' is private"));
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .arg("tasks")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(aliases: t)"))
+        .stdout(predicate::str::contains("prepare-db").not());
+}
+
+#[test]
+fn test_confirm_requires_yes_without_tty() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.publish]
+cmd = "echo published"
+confirm = "Push a tag to origin?"
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "publish"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Task 'publish' requires confirmation"))
+        .stdout(predicate::str::contains("published").not());
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "publish", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("published"));
+}
+
+#[test]
+fn test_many_parallel_tasks_complete_and_signals_fail() {
+    let temp = tempdir().unwrap();
+    let mut toml = String::new();
+    let mut names = Vec::new();
+    for i in 0..40 {
+        toml.push_str(&format!("[tasks.t{i}]\ncmd = \"true\"\n\n"));
+        names.push(format!("\"t{i}\""));
+    }
+    toml.push_str(&format!("[tasks.all]\ncmd = \"echo done\"\ndepends_on = [{}]\n", names.join(", ")));
+    toml.push_str("\n[tasks.killed]\ncmd = \"kill -9 $$\"\n");
+    fs::write(temp.path().join("zetten.toml"), toml).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "all", "-w", "8"])
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("41 succeeded"));
+
+    if cfg!(unix) {
+        cargo_bin_cmd!("ztn")
+            .current_dir(&temp)
+            .args(["run", "killed"])
+            .assert()
+            .code(137)
+            .stdout(predicate::str::contains("killed (exit code 137)"));
+    }
+}
+
+#[test]
+fn test_fail_fast_cancels_and_keep_going_continues() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.broken]
+cmd = "sh -c 'exit 3'"
+
+[tasks.slow]
+cmd = "sleep 5 && echo slow finished"
+
+[tasks.after-broken]
+cmd = "echo should never run"
+depends_on = ["broken"]
+
+[tasks.independent]
+cmd = "echo independent ran"
+depends_on = ["slow"]
+"#).unwrap();
+
+    // Fail-fast: the in-flight sleep is killed and everything else is cancelled
+    let started = std::time::Instant::now();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "after-broken", "independent", "-w", "2"])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("slow (cancelled)"))
+        .stdout(predicate::str::contains("0 succeeded, 0 cached, 0 skipped, 0 warned, 1 failed, 3 cancelled"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(4));
+
+    // Keep-going: only the failure's dependents are left out
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.broken]
+cmd = "sh -c 'exit 3'"
+
+[tasks.after-broken]
+cmd = "echo should never run"
+depends_on = ["broken"]
+
+[tasks.independent]
+cmd = "echo independent ran"
+"#).unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "after-broken", "independent", "-w", "1", "--keep-going"])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("independent ran"))
+        .stdout(predicate::str::contains("should never run").not())
+        .stdout(predicate::str::contains("1 succeeded, 0 cached, 0 skipped, 0 warned, 1 failed, 1 cancelled"));
+}
+
+#[test]
+fn test_resources_prevent_concurrent_claims() {
+    let temp = tempdir().unwrap();
+    // Each task fails if another one holds the "database" at the same time
+    let claim = "mkdir held-{r} && sleep 0.3 && rmdir held-{r}";
+    fs::write(temp.path().join("zetten.toml"), format!(r#"
+[resources]
+gpu = 2
+
+[tasks.itest-a]
+cmd = "{db}"
+lock = "postgres"
+
+[tasks.itest-b]
+cmd = "{db}"
+resources = ["postgres", "port:8000"]
+
+[tasks.train]
+cmd = "echo training"
+resources = {{ gpu = 2 }}
+
+[tasks.all]
+cmd = "echo all done"
+depends_on = ["itest-a", "itest-b", "train"]
+"#, db = claim.replace("{r}", "postgres"))).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "all", "-w", "4"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("4 succeeded"));
+
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.train]
+cmd = "echo training"
+resources = { gpu = 3 }
+"#).unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "train"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("claims 3 of resource 'gpu', but only 1 available"));
+}
+
+#[test]
+fn test_weighted_and_serial_tasks_share_worker_budget() {
+    let temp = tempdir().unwrap();
+    // Each task fails if the other one is running at the same moment
+    let alone = "mkdir busy && sleep 0.3 && rmdir busy";
+    fs::write(temp.path().join("zetten.toml"), format!(r#"
+[tasks.mypy]
+cmd = "{alone}"
+parallel = false
+
+[tasks.pytest]
+cmd = "{alone}"
+weight = 4
+"#)).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "mypy", "pytest", "-w", "4"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 succeeded"));
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "mypy", "-w", "0"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_history_orders_ready_tasks_by_critical_path() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.alpha]
+cmd = "echo run-alpha"
+
+[tasks.beta]
+cmd = "echo run-beta"
+
+[tasks.gamma]
+cmd = "echo run-gamma"
+
+[tasks.package]
+cmd = "echo run-package"
+depends_on = ["gamma"]
+"#).unwrap();
+    fs::create_dir(temp.path().join(".zetten")).unwrap();
+    fs::write(temp.path().join(".zetten/history.toml"), r#"
+[durations_ms]
+alpha = 100
+beta = 3000
+gamma = 1000
+package = 2500
+"#).unwrap();
+
+    let output = cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "alpha", "beta", "package", "-w", "1"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pos = |s: &str| stdout.find(s).unwrap();
+    // gamma → package (3.5s) beats beta (3s), which beats alpha (0.1s)
+    assert!(pos("run-gamma") < pos("run-beta"), "{}", stdout);
+    assert!(pos("run-beta") < pos("run-alpha"), "{}", stdout);
+
+    let history = fs::read_to_string(temp.path().join(".zetten/history.toml")).unwrap();
+    assert!(history.contains("alpha = "));
+}
+
+#[test]
+fn test_jobserver_client_and_server() {
+    if !cfg!(unix) || std::process::Command::new("make").arg("--version").output().is_err() {
+        return;
+    }
+    let temp = tempdir().unwrap();
+    fs::create_dir(temp.path().join("running")).unwrap();
+    // Records how many tasks are running whenever one starts
+    let probe = |n: &str| format!(
+        "touch running/{n} && ls running | wc -l >> counts && sleep 0.3 && rm running/{n}"
+    );
+    fs::write(temp.path().join("zetten.toml"), format!(r#"
+[tasks.a]
+cmd = "{}"
+[tasks.b]
+cmd = "{}"
+[tasks.c]
+cmd = "{}"
+[tasks.d]
+cmd = "{}"
+
+[tasks.heavy]
+cmd = "{}"
+parallel = false
+[tasks.light]
+cmd = "{}"
+weight = 3
+
+[tasks.flags]
+cmd = "echo flags=$MAKEFLAGS"
+"#, probe("a"), probe("b"), probe("c"), probe("d"), probe("heavy"), probe("light"))).unwrap();
+
+    // Client: `make -j2` hands out two slots, so four workers never run more than two tasks
+    let ztn = assert_cmd::cargo::cargo_bin!("ztn");
+    fs::write(temp.path().join("Makefile"), format!("all:\n\t+{} run a b c d -w 4\n", ztn.display())).unwrap();
+    let status = std::process::Command::new("make")
+        .args(["-j2", "-s"])
+        .current_dir(&temp)
+        .env_remove("MAKEFLAGS")
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let counts = fs::read_to_string(temp.path().join("counts")).unwrap();
+    assert!(counts.lines().all(|c| c.trim().parse::<u32>().unwrap() <= 2), "{}", counts);
+
+    // A task wanting more slots than make has gets all of them instead of
+    // waiting forever, here the exclusive `heavy` next to `light`
+    fs::write(temp.path().join("Makefile"), format!("all:\n\t+{} run heavy light -w 4\n", ztn.display())).unwrap();
+    let mut make = std::process::Command::new("make")
+        .args(["-j2", "-s"])
+        .current_dir(&temp)
+        .env_remove("MAKEFLAGS")
+        .spawn()
+        .unwrap();
+    let started = std::time::Instant::now();
+    let status = loop {
+        if let Some(status) = make.try_wait().unwrap() {
+            break status;
+        }
+        if started.elapsed() > std::time::Duration::from_secs(20) {
+            let _ = make.kill();
+            panic!("make -j2 with an exclusive task hung");
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    };
+    assert!(status.success());
+
+    // Server: tasks see a jobserver sized to --workers
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .env_remove("MAKEFLAGS")
+        .args(["run", "flags", "-w", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("flags= -j3 --jobserver-fds="));
+}
+
+#[test]
+fn test_nested_runs_share_the_session() {
+    let temp = tempdir().unwrap();
+    let ztn = assert_cmd::cargo::cargo_bin!("ztn");
+    fs::write(temp.path().join("zetten.toml"), format!(r#"
+[tasks.gen]
+cmd = "sh -c 'echo generated ${{MODE:-debug}} >> gen.log'"
+
+[tasks.build]
+cmd = "{ztn} run gen lint -w 1"
+depends_on = ["gen"]
+
+[tasks.release]
+cmd = "{ztn} run gen -k MODE=release -w 1"
+depends_on = ["gen"]
+
+[tasks.lint]
+cmd = "echo linting"
+
+[tasks.loop]
+cmd = "{ztn} run again -w 1"
+
+[tasks.again]
+cmd = "{ztn} run loop -w 1"
+
+[tasks.slow-gen]
+cmd = "sh -c 'sleep 1; echo slow >> slow.log'"
+
+[tasks.concurrent]
+cmd = "{ztn} run slow-gen -w 1"
+
+[tasks.ping]
+cmd = "{ztn} run pong -w 1"
+
+[tasks.pong]
+cmd = "{ztn} run ping -w 1"
+"#, ztn = ztn.display())).unwrap();
+
+    // The nested run reuses `gen` from its parent and reports back to it
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "build", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nested runs:"))
+        .stdout(predicate::str::contains("build ↳ gen (cached)"))
+        .stdout(predicate::str::contains("build ↳ lint (ok)"));
+    assert_eq!(fs::read_to_string(temp.path().join("gen.log")).unwrap(), "generated debug\n");
+    assert!(fs::read_dir(temp.path().join(".zetten/sessions")).unwrap().next().is_none());
+
+    // Other variables make it other work, which the nested run does itself
+    fs::remove_file(temp.path().join("gen.log")).unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "release", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("release ↳ gen (ok)"));
+    assert_eq!(fs::read_to_string(temp.path().join("gen.log")).unwrap(), "generated debug\ngenerated release\n");
+
+    // A task that ends up starting itself again is caught instead of forking forever
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "loop", "-w", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Recursive ztn invocation"))
+        .stderr(predicate::str::contains("loop ➔ again"));
+
+    // Work the parent is still doing is waited for rather than run twice
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "slow-gen", "concurrent", "-w", "2"])
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp.path().join("slow.log")).unwrap(), "slow\n");
+
+    // Two tasks that would wait on each other through nested runs fail instead of hanging
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "ping", "pong", "-w", "2"])
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Recursive ztn invocation"));
+    assert!(fs::read_dir(temp.path().join(".zetten/sessions")).unwrap().next().is_none());
+}
+
+#[test]
+fn test_shared_setup_and_teardown_run_once_around_users() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.db-up]
+cmd = "sh -c 'echo up >> events'"
+private = true
+
+[tasks.db-down]
+cmd = "sh -c 'echo down >> events'"
+private = true
+
+[tasks.api]
+cmd = "sh -c 'echo api >> events'"
+setup = "db-up"
+teardown = "db-down"
+
+[tasks.worker]
+cmd = "sh -c 'echo worker >> events; exit 2'"
+setup = "db-up"
+teardown = "db-down"
+
+[tasks.report]
+cmd = "echo report"
+depends_on = ["worker"]
+"#).unwrap();
+
+    // Teardown still runs after a failure and shows up in the summary
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "api", "worker", "report", "--keep-going"])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("3 succeeded, 0 cached, 0 skipped, 0 warned, 1 failed, 1 cancelled"));
+
+    let events = fs::read_to_string(temp.path().join("events")).unwrap();
+    let events: Vec<&str> = events.lines().collect();
+    assert_eq!(events.iter().filter(|e| **e == "up").count(), 1);
+    assert_eq!(events.iter().filter(|e| **e == "down").count(), 1);
+    assert_eq!(events.first(), Some(&"up"));
+    assert_eq!(events.last(), Some(&"down"));
+
+    // Fail-fast cancels the rest of the plan, but not the cleanup
+    fs::remove_file(temp.path().join("events")).unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "worker", "report", "-w", "4"])
+        .assert()
+        .code(2);
+    assert_eq!(fs::read_to_string(temp.path().join("events")).unwrap(), "up\nworker\ndown\n");
+
+    // So does a task that cannot even be started
+    fs::remove_file(temp.path().join("events")).unwrap();
+    let config = fs::read_to_string(temp.path().join("zetten.toml")).unwrap();
+    let config = config.replace("cmd = \"sh -c 'echo api >> events'\"", "cmd = \"sh -c 'echo api >> events'\"\nrun_if = { glob = \"src/[\" }");
+    fs::write(temp.path().join("zetten.toml"), config).unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "api", "-w", "1"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("Task 'api' could not run"));
+    assert_eq!(fs::read_to_string(temp.path().join("events")).unwrap(), "up\ndown\n");
+}
+
+#[test]
+fn test_service_is_ready_before_dependents_and_stopped_after() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.server]
+cmd = "sh -c 'echo booting; sleep 0.3; touch up; echo Application startup complete; sleep 30'"
+service = true
+ready_when = { log_regex = "startup complete$" }
+
+[tasks.smoke]
+cmd = "test -f up"
+depends_on = ["server"]
+
+[tasks.crash]
+cmd = "sh -c 'echo oops; exit 4'"
+service = true
+ready_when = { port = 1, timeout = 5 }
+
+[tasks.needs-crash]
+cmd = "echo unreachable"
+depends_on = ["crash"]
+"#).unwrap();
+
+    // Finishes long before the service's own 30s sleep would
+    let start = std::time::Instant::now();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "smoke"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 succeeded"));
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+    // A service that exits before it is ready fails its dependents
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "needs-crash"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("exited before it was ready"));
+}
+
+#[test]
+fn test_after_and_optional_dependencies_only_order_planned_tasks() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.format]
+cmd = "sh -c 'sleep 0.2; echo format >> order; exit ${FAIL:-0}'"
+
+[tasks.lint]
+cmd = "sh -c 'echo lint >> order'"
+after = ["format"]
+
+[tasks.flaky]
+cmd = "sh -c 'echo flaky >> order; exit 3'"
+
+[tasks.test]
+cmd = "sh -c 'echo test >> order'"
+optional_depends_on = ["flaky"]
+"#).unwrap();
+    let order = || fs::read_to_string(temp.path().join("order")).unwrap();
+
+    // `after` doesn't pull format in...
+    cargo_bin_cmd!("ztn").current_dir(&temp).args(["run", "lint"]).assert().success();
+    assert_eq!(order(), "lint\n");
+
+    // ...but orders it first when both are requested, even with free workers
+    fs::remove_file(temp.path().join("order")).unwrap();
+    cargo_bin_cmd!("ztn").current_dir(&temp).args(["run", "lint", "format", "-w", "4"]).assert().success();
+    assert_eq!(order(), "format\nlint\n");
+
+    // A failing `after` task doesn't cancel lint with --keep-going
+    fs::remove_file(temp.path().join("order")).unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "lint", "format", "-k", "FAIL=1", "--keep-going"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("1 succeeded, 0 cached, 0 skipped, 0 warned, 1 failed, 0 cancelled"));
+    assert_eq!(order(), "format\nlint\n");
+
+    // A failing optional dependency still runs first but is only a warning,
+    // so it neither blocks its dependent nor triggers fail-fast
+    fs::remove_file(temp.path().join("order")).unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "test", "flaky", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 succeeded, 0 cached, 0 skipped, 1 warned, 0 failed, 0 cancelled"));
+    assert_eq!(order(), "flaky\ntest\n");
+}
+
+#[test]
+fn test_dependencies_with_args_and_vars_are_separate_instances() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("src.txt"), "v1").unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.build]
+cmd = "echo building ${TARGET:-sdist}"
+inputs = ["src.txt"]
+
+[tasks.publish]
+cmd = "echo publishing"
+depends_on = ["build", { task = "build", args = ["--release"], vars = { TARGET = "wheel" } }]
+
+[tasks.smoke]
+cmd = "echo smoke"
+depends_on = [{ task = "build", vars = { TARGET = "wheel" }, args = ["--release"] }]
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "publish", "smoke", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("building sdist\n"))
+        .stdout(predicate::str::contains("building wheel --release"))
+        .stdout(predicate::str::contains("build[--release TARGET=wheel] (ok)"))
+        // Both dependents share the one instance
+        .stdout(predicate::str::contains("4 succeeded"));
+
+    // Each instance keeps its own cache entry
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "publish", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 succeeded, 2 cached"));
+}
+
+#[test]
+fn test_matrix_expands_into_filtered_instances() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.test]
+cmd = "echo testing py${matrix.python} on ${matrix.db}"
+matrix = { python = ["3.11", "3.12"], db = ["sqlite", "postgres"] }
+
+[tasks.report]
+cmd = "echo report"
+depends_on = ["test"]
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "report", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("testing py3.11 on sqlite"))
+        .stdout(predicate::str::contains("testing py3.12 on postgres"))
+        .stdout(predicate::str::contains("test[db=postgres python=3.11] (ok)"))
+        .stdout(predicate::str::contains("5 succeeded"));
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "test", "--matrix", "python=3.12", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("testing py3.12 on sqlite"))
+        .stdout(predicate::str::contains("testing py3.12 on postgres"))
+        .stdout(predicate::str::contains("py3.11").not())
+        .stdout(predicate::str::contains("2 succeeded"));
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "test", "--matrix", "python=2.7"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No matrix combination"));
+
+    // The same holds when the matrix is only a dependency
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "report", "--matrix", "python=2.7"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No matrix combination of 'test'"));
+
+    // A misspelled axis is rejected rather than ignored
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "report", "--matrix", "pyton=3.12"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("matrix axis 'pyton'"));
+}
+
+#[test]
+fn test_rerun_repeats_only_failed_and_cancelled_tasks() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.ok]
+cmd = "sh -c 'echo ok >> log'"
+
+[tasks.flaky]
+cmd = "sh -c 'echo flaky-${MODE} >> log; test -f fixed'"
+
+[tasks.after-flaky]
+cmd = "sh -c 'echo after >> log'"
+depends_on = ["flaky"]
+"#).unwrap();
+    let log = || fs::read_to_string(temp.path().join("log")).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "ok", "after-flaky", "-k", "MODE=strict", "-w", "1", "--keep-going"])
+        .assert()
+        .failure();
+    assert!(log().contains("ok\n") && log().contains("flaky-strict\n") && !log().contains("after"));
+
+    // Same vars as before, and only what didn't succeed
+    fs::write(temp.path().join("fixed"), "").unwrap();
+    fs::remove_file(temp.path().join("log")).unwrap();
+    cargo_bin_cmd!("ztn").current_dir(&temp).arg("rerun").assert().success();
+    assert_eq!(log(), "flaky-strict\nafter\n");
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "--failed"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing to re-run"));
+}
+
+#[test]
+fn test_only_from_and_until_narrow_the_plan() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.format]
+cmd = "sh -c 'echo format >> log'"
+
+[tasks.lint]
+cmd = "sh -c 'echo lint >> log'"
+depends_on = ["format"]
+
+[tasks.test]
+cmd = "sh -c 'echo test >> log'"
+depends_on = ["lint"]
+
+[tasks.package]
+cmd = "sh -c 'echo package >> log'"
+depends_on = ["test"]
+"#).unwrap();
+    let run = |extra: &[&str]| {
+        let _ = fs::remove_file(temp.path().join("log"));
+        cargo_bin_cmd!("ztn").current_dir(&temp).arg("run").args(extra).assert().success();
+        fs::read_to_string(temp.path().join("log")).unwrap()
+    };
+
+    assert_eq!(run(&["test", "--only"]), "test\n");
+    assert_eq!(run(&["package", "--from", "lint"]), "lint\ntest\npackage\n");
+    assert_eq!(run(&["package", "--until", "lint"]), "format\nlint\n");
+    assert_eq!(run(&["package", "--from", "lint", "--until", "test"]), "lint\ntest\n");
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "lint", "--from", "package"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not part of this run"));
+}
+
+#[test]
+fn test_from_and_until_keep_the_setup_and_teardown_of_a_kept_task() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.db-up]
+cmd = "sh -c 'echo up >> log'"
+
+[tasks.db-down]
+cmd = "sh -c 'echo down >> log'"
+
+[tasks.api]
+cmd = "sh -c 'echo api >> log'"
+setup = "db-up"
+teardown = "db-down"
+
+[tasks.report]
+cmd = "sh -c 'echo report >> log'"
+depends_on = ["api"]
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "report", "--until", "api"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp.path().join("log")).unwrap(), "up\napi\ndown\n");
+
+    fs::remove_file(temp.path().join("log")).unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "api", "--from", "api"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(temp.path().join("log")).unwrap(), "up\napi\ndown\n");
+
+    // A reversed range is a mistake, not an empty run
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "report", "--from", "report", "--until", "api"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("'report' comes after 'api'"));
+}
+
+#[test]
+fn test_affected_selects_tasks_touched_by_git_changes() {
+    let temp = tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@t", "-c", "commit.gpgsign=false"])
+            .args(args)
+            .current_dir(&temp)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
+    };
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+    fs::create_dir_all(temp.path().join("docs")).unwrap();
+    fs::write(temp.path().join("src/app.py"), "v1").unwrap();
+    fs::write(temp.path().join("docs/index.md"), "v1").unwrap();
+    fs::write(temp.path().join(".gitignore"), ".zetten/\n").unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.test]
+cmd = "echo testing"
+inputs = ["src/"]
+
+[tasks.package]
+cmd = "echo packaging"
+depends_on = ["test"]
+
+[tasks.docs]
+cmd = "echo building docs"
+inputs = ["docs/**/*.md"]
+
+[tasks.ci]
+cmd = "echo all checks done"
+depends_on = ["test", "docs"]
+"#).unwrap();
+    git(&["init", "-q", "-b", "main"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "init"]);
+    git(&["checkout", "-q", "-b", "feature"]);
+
+    // A committed docs-only change leaves the test suite alone, even though
+    // the aggregate that depends on both is affected
+    fs::write(temp.path().join("docs/index.md"), "v2").unwrap();
+    git(&["commit", "-q", "-am", "docs"]);
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "--affected", "--base", "main", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("building docs"))
+        .stdout(predicate::str::contains("all checks done"))
+        .stdout(predicate::str::contains("testing").not());
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "ci", "--affected", "--base", "main", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("✔ docs (cached)"))
+        .stdout(predicate::str::contains("testing").not());
+
+    // Without a base only uncommitted work counts: here a source change, which
+    // also pulls in its dependents
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "--affected"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No tasks affected"));
+    fs::write(temp.path().join("src/app.py"), "v2").unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "--affected", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("packaging"))
+        .stdout(predicate::str::contains("building docs").not());
+}
+
+#[test]
+fn test_changed_files_are_passed_to_tasks() {
+    let temp = tempdir().unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@t", "-c", "commit.gpgsign=false"])
+            .args(args)
+            .current_dir(&temp)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
+    };
+    fs::write(temp.path().join("a.py"), "v1").unwrap();
+    fs::write(temp.path().join("notes.txt"), "v1").unwrap();
+    fs::create_dir_all(temp.path().join("docs")).unwrap();
+    fs::write(temp.path().join("docs/index.md"), "v1").unwrap();
+    fs::write(temp.path().join(".gitignore"), ".zetten/\n").unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.lint]
+cmd = "echo linting"
+inputs = ["*.py"]
+pass_files = true
+
+[tasks.staged]
+cmd = "echo staged=${ZTN_STAGED_FILES}"
+inputs = ["*.py"]
+
+[tasks.docs]
+cmd = "echo checking docs"
+inputs = ["docs/**"]
+pass_files = true
+"#).unwrap();
+    git(&["init", "-q", "-b", "main"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "init"]);
+
+    fs::write(temp.path().join("a.py"), "v2").unwrap();
+    fs::write(temp.path().join("notes.txt"), "v2").unwrap();
+    fs::write(temp.path().join("my file.py"), "new").unwrap();
+    git(&["add", "a.py"]);
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "lint", "staged", "docs", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("linting a.py my file.py"))
+        .stdout(predicate::str::contains("staged=a.py"))
+        .stdout(predicate::str::contains("checking docs").not());
+}
+
+#[test]
+fn test_hooks_install_runs_tasks_and_keeps_existing_hooks() {
+    let temp = tempdir().unwrap();
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@t", "-c", "commit.gpgsign=false"])
+            .args(args)
+            .current_dir(&temp)
+            .output()
+            .unwrap()
+            .status
+    };
+    fs::write(temp.path().join(".gitignore"), ".zetten/\n").unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.lint]
+cmd = "sh -c 'test ! -e broken && touch linted'"
+hooks = ["pre-commit"]
+"#).unwrap();
+    assert!(git(&["init", "-q", "-b", "main"]).success());
+    let hook = temp.path().join(".git/hooks/pre-commit");
+    fs::create_dir_all(hook.parent().unwrap()).unwrap();
+    fs::write(&hook, "#!/bin/sh\ntouch legacy\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["hooks", "install"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Installed pre-commit hook"));
+    assert!(temp.path().join(".git/hooks/pre-commit.pre-ztn").exists());
+
+    // Both the old hook and the task run on commit, and a failing task blocks it
+    assert!(git(&["add", "."]).success());
+    assert!(git(&["commit", "-q", "-m", "init"]).success());
+    assert!(temp.path().join("legacy").exists());
+    assert!(temp.path().join("linted").exists());
+    fs::write(temp.path().join("broken"), "").unwrap();
+    assert!(git(&["add", "."]).success());
+    assert!(!git(&["commit", "-q", "-m", "broken"]).success());
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["hooks", "uninstall"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&hook).unwrap(), "#!/bin/sh\ntouch legacy\n");
+    assert!(!temp.path().join(".git/hooks/pre-commit.pre-ztn").exists());
+}
+
+#[test]
+fn test_hook_tasks_see_the_hook_args_and_stdin() {
+    let temp = tempdir().unwrap();
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(["-c", "user.name=t", "-c", "user.email=t@t", "-c", "commit.gpgsign=false"])
+            .args(args)
+            .current_dir(&temp)
+            .output()
+            .unwrap()
+            .status
+    };
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.check-msg]
+cmd = "sh -c 'grep -q ticket ${ZTN_HOOK_ARGS}'"
+hooks = ["commit-msg"]
+
+[tasks.check-push]
+cmd = '''sh -c 'printf "%s|%s" "$ZTN_HOOK_ARGS" "$ZTN_HOOK_STDIN" | cut -d" " -f1-2 > seen' '''
+hooks = ["pre-push"]
+"#).unwrap();
+    assert!(git(&["init", "-q", "-b", "main"]).success());
+    assert!(git(&["init", "-q", "--bare", "remote.git"]).success());
+    fs::write(temp.path().join(".gitignore"), ".zetten/\nseen\nlegacy\nremote.git/\n").unwrap();
+    let legacy = temp.path().join(".git/hooks/pre-push");
+    fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+    fs::write(&legacy, "#!/bin/sh\ncat > legacy\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&legacy, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    cargo_bin_cmd!("ztn").current_dir(&temp).args(["hooks", "install"]).assert().success();
+
+    // commit-msg gets the message file as its argument
+    assert!(git(&["add", "."]).success());
+    assert!(!git(&["commit", "-q", "-m", "no reference"]).success());
+    assert!(git(&["commit", "-q", "-m", "ticket 42"]).success());
+
+    // pre-push gets the remote as arguments and the refs on stdin, which the
+    // previous hook also still reads
+    assert!(git(&["push", "-q", "remote.git", "main"]).success());
+    assert_eq!(fs::read_to_string(temp.path().join("seen")).unwrap(), "remote.git remote.git|refs/heads/main\n");
+    assert!(fs::read_to_string(temp.path().join("legacy")).unwrap().starts_with("refs/heads/main "));
+}
+
+#[test]
+fn test_tag_expressions_select_tasks() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.lint]
+cmd = "echo linting"
+tags = ["ci", "fast"]
+
+[tasks.e2e]
+cmd = "echo end to end"
+tags = ["ci", "slow"]
+
+[tasks.bench]
+cmd = "echo benchmarking"
+tags = ["nightly", "slow"]
+
+[tasks.fmt]
+cmd = "echo formatting"
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["tasks", "--tag", "(ci or nightly) and not slow"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("lint"))
+        .stdout(predicate::str::contains("e2e").not())
+        .stdout(predicate::str::contains("bench").not())
+        .stdout(predicate::str::contains("fmt").not());
+
+    // The compact operators still work
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "-w", "1", "--tag", "ci+!slow,nightly"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("linting"))
+        .stdout(predicate::str::contains("benchmarking"))
+        .stdout(predicate::str::contains("end to end").not());
+}
+
+#[test]
+fn test_glob_and_regex_patterns_select_task_families() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.lint-ruff]
+cmd = "echo ruff"
+
+[tasks.lint-mypy]
+cmd = "echo mypy"
+
+[tasks.test-unit]
+cmd = "echo unit"
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "lint-*", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ruff"))
+        .stdout(predicate::str::contains("mypy"))
+        .stdout(predicate::str::contains("unit").not());
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "--match", "^test-", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("unit"))
+        .stdout(predicate::str::contains("ruff").not());
+
+    // Matching nothing is an error unless explicitly allowed
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "docs-*"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No task matches 'docs-*'"));
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "--match", "^docs-", "--allow-empty"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No tasks match"));
+}
+
+#[test]
+fn test_group_task_runs_dependencies_without_a_command() {
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.lint]
+cmd = "echo linting"
+
+[tasks.test]
+cmd = "echo testing"
+
+[tasks.ci]
+depends_on = ["lint", "test"]
+"#).unwrap();
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "ci", "-w", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("linting"))
+        .stdout(predicate::str::contains("testing"))
+        .stdout(predicate::str::contains("ci (group: 2 ok)"))
+        .stdout(predicate::str::contains("2 succeeded"));
+
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "ci", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[ci] (group: lint, test)"));
+
+    // A task with nothing to run and nothing to group is a mistake
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.empty]
+description = "forgot the command"
+"#).unwrap();
+    cargo_bin_cmd!("ztn")
+        .current_dir(&temp)
+        .args(["run", "empty"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("has neither 'cmd' nor 'script'"));
+}
+
+#[test]
+fn test_ctrl_c_runs_each_pending_teardown_once() {
+    if !cfg!(unix) {
+        return;
+    }
+    let temp = tempdir().unwrap();
+    fs::write(temp.path().join("zetten.toml"), r#"
+[tasks.db-up]
+cmd = "sh -c 'echo up >> events; sleep ${UP_DELAY:-0}'"
+
+[tasks.db-down]
+cmd = "sh -c 'echo down-start >> events; sleep 0.5; echo down-end >> events'"
+
+[tasks.api]
+cmd = "sleep 30"
+setup = "db-up"
+teardown = "db-down"
+"#).unwrap();
+
+    // Interrupted while the task runs, then while its setup still does
+    for delay in ["0", "30"] {
+        let events = temp.path().join("events");
+        let _ = fs::remove_file(&events);
+        let mut ztn = std::process::Command::new(assert_cmd::cargo::cargo_bin!("ztn"))
+            .args(["run", "api", "-w", "1", "-k", &format!("UP_DELAY={}", delay)])
+            .current_dir(&temp)
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        while !fs::read_to_string(&events).unwrap_or_default().contains("up") {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        let interrupted = std::process::Command::new("kill").args(["-INT", &ztn.id().to_string()]).status().unwrap();
+        assert!(interrupted.success());
+
+        assert_eq!(ztn.wait().unwrap().code(), Some(130));
+        std::thread::sleep(std::time::Duration::from_millis(700));
+        assert_eq!(fs::read_to_string(&events).unwrap(), "up\ndown-start\ndown-end\n", "UP_DELAY={}", delay);
+        // The session directory goes too, even though the process exits from the handler
+        assert!(fs::read_dir(temp.path().join(".zetten/sessions")).unwrap().next().is_none());
+    }
+}